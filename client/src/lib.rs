@@ -1,8 +1,9 @@
+use std::collections::HashMap;
 use std::error;
 use std::fmt;
 use std::io;
-use std::net::{ToSocketAddrs, UdpSocket};
-use std::time::Duration;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
 
 use postcard::experimental::schema::Schema;
 use postcard::{self, from_bytes};
@@ -26,6 +27,7 @@ impl<T> ConnHealth<T> {
 pub struct Client {
     sock: Option<UdpSocket>,
     retries: u8,
+    seq_no: u32,
 }
 
 #[derive(Debug)]
@@ -38,6 +40,9 @@ pub enum Error {
     // FIXME: Do something like ErrorKind for I/O, getting info from Error
     // socket?
     RequestFailed(RequestError),
+    DeviceFailed(DeviceError),
+    ConfigFailed(ConfigError),
+    ScheduleFailed(ScheduleError),
 }
 
 impl fmt::Display for Error {
@@ -49,6 +54,9 @@ impl fmt::Display for Error {
             Error::NoResponse(_) => write!(f, "no response from server before timeout"),
             Error::Parse(_) => write!(f, "could not ser/deserialize RPC call"),
             Error::RequestFailed(_) => write!(f, "server saw request but failed to process it"),
+            Error::DeviceFailed(d) => write!(f, "device rejected request: {d}"),
+            Error::ConfigFailed(c) => write!(f, "device config change rejected: {c}"),
+            Error::ScheduleFailed(s) => write!(f, "server rejected schedule: {s}"),
         }
     }
 }
@@ -60,6 +68,9 @@ impl error::Error for Error {
             Error::Io(e) => Some(e),
             Error::Parse(p) => Some(p),
             Error::RequestFailed(r) => Some(r),
+            Error::DeviceFailed(d) => Some(d),
+            Error::ConfigFailed(c) => Some(c),
+            Error::ScheduleFailed(s) => Some(s),
         }
     }
 }
@@ -88,6 +99,7 @@ impl Client {
         Self {
             sock: None,
             retries: 0,
+            seq_no: 0,
         }
     }
 
@@ -128,7 +140,7 @@ impl Client {
 
         resp.0
             .map(|_| ConnHealth((), retries))
-            .map_err(|r| Error::RequestFailed(r))
+            .map_err(Error::DeviceFailed)
     }
 
     pub fn notify<N>(&mut self, notify: N, buf: &mut [u8]) -> Result<ConnHealth<()>, Error>
@@ -140,7 +152,7 @@ impl Client {
 
         resp.0
             .map(|_| ConnHealth((), retries))
-            .map_err(|r| Error::RequestFailed(r))
+            .map_err(Error::DeviceFailed)
     }
 
     pub fn ack<A>(&mut self, ack: A, buf: &mut [u8]) -> Result<ConnHealth<()>, Error>
@@ -152,7 +164,7 @@ impl Client {
 
         resp.0
             .map(|_| ConnHealth((), retries))
-            .map_err(|r| Error::RequestFailed(r))
+            .map_err(Error::DeviceFailed)
     }
 
     pub fn set_dimming<PWM>(&mut self, pwm: PWM, buf: &mut [u8]) -> Result<ConnHealth<()>, Error>
@@ -164,7 +176,7 @@ impl Client {
 
         resp.0
             .map(|_| ConnHealth((), retries))
-            .map_err(|r| Error::RequestFailed(r))
+            .map_err(Error::DeviceFailed)
     }
 
     pub fn set_backlight<B>(&mut self, back: B, buf: &mut [u8]) -> Result<ConnHealth<()>, Error>
@@ -180,7 +192,7 @@ impl Client {
 
         resp.0
             .map(|_| ConnHealth((), retries))
-            .map_err(|r| Error::RequestFailed(r))
+            .map_err(Error::DeviceFailed)
     }
 
     pub fn send_msg<M>(&mut self, msg: M, buf: &mut [u8]) -> Result<ConnHealth<()>, Error>
@@ -195,6 +207,157 @@ impl Client {
             .map_err(|r| Error::RequestFailed(r))
     }
 
+    /// Runs the server's bargraph self-test, validating the hardware and
+    /// restoring whatever it was showing beforehand.
+    pub fn self_test(&mut self, buf: &mut [u8]) -> Result<ConnHealth<()>, Error> {
+        let (resp, retries): (SelfTestResponse, _) =
+            self.raw::<SelfTest, SelfTestResponse, _, _, _>(SELF_TEST_PATH, SelfTest {}, buf)?;
+
+        resp.0
+            .map(|_| ConnHealth((), retries))
+            .map_err(Error::DeviceFailed)
+    }
+
+    /// Lists the devices currently configured on the server, whether set
+    /// at startup or added since via [`Self::add_device`].
+    pub fn list_devices(&mut self, buf: &mut [u8]) -> Result<ConnHealth<Vec<Device>>, Error> {
+        let (resp, retries): (ListDevicesResponse, _) = self
+            .raw::<ListDevices, ListDevicesResponse, _, _, _>(LIST_DEVICES_PATH, ListDevices {}, buf)?;
+
+        Ok(ConnHealth(resp.0, retries))
+    }
+
+    /// Brings `device` online and persists it to the server's device
+    /// config, so it survives a restart without needing to be re-added.
+    pub fn add_device<DEV>(&mut self, device: DEV, buf: &mut [u8]) -> Result<ConnHealth<()>, Error>
+    where
+        DEV: Into<AddDevice>,
+    {
+        let (resp, retries): (AddDeviceResponse, _) =
+            self.raw::<AddDevice, AddDeviceResponse, _, _, _>(ADD_DEVICE_PATH, device.into(), buf)?;
+
+        resp.0
+            .map(|_| ConnHealth((), retries))
+            .map_err(Error::ConfigFailed)
+    }
+
+    /// Takes the device named `name` offline and removes it from the
+    /// server's device config.
+    pub fn remove_device<N>(&mut self, name: N, buf: &mut [u8]) -> Result<ConnHealth<()>, Error>
+    where
+        N: Into<RemoveDevice>,
+    {
+        let (resp, retries): (RemoveDeviceResponse, _) = self
+            .raw::<RemoveDevice, RemoveDeviceResponse, _, _, _>(
+                REMOVE_DEVICE_PATH,
+                name.into(),
+                buf,
+            )?;
+
+        resp.0
+            .map(|_| ConnHealth((), retries))
+            .map_err(Error::ConfigFailed)
+    }
+
+    /// Asks the server what it last recorded for the `(seq_no, key)` of a
+    /// previous request, e.g. one that only got back an opaque
+    /// `RequestError {}` or never got a reply at all before timing out.
+    pub fn error_query(
+        &mut self,
+        seq_no: u32,
+        key: Key,
+        buf: &mut [u8],
+    ) -> Result<ConnHealth<Option<DispatchError>>, Error> {
+        let (resp, retries): (LastErrorResponse, _) = self
+            .raw::<ErrorQuery, LastErrorResponse, _, _, _>(
+                ERROR_QUERY_PATH,
+                ErrorQuery { seq_no, key },
+                buf,
+            )?;
+
+        Ok(ConnHealth(resp.0, retries))
+    }
+
+    /// Queues `notify` to fire later, per `schedule`, instead of applying
+    /// it immediately. The server applies it on its own timer; this just
+    /// confirms the server accepted the request.
+    pub fn schedule_notify<S>(&mut self, req: S, buf: &mut [u8]) -> Result<ConnHealth<()>, Error>
+    where
+        S: Into<ScheduleNotify>,
+    {
+        let (resp, retries): (ScheduleNotifyResponse, _) = self
+            .raw::<ScheduleNotify, ScheduleNotifyResponse, _, _, _>(
+                SCHEDULE_NOTIFY_PATH,
+                req.into(),
+                buf,
+            )?;
+
+        resp.0
+            .map(|_| ConnHealth((), retries))
+            .map_err(Error::ScheduleFailed)
+    }
+
+    /// Finds `wb-notifier` daemons on the LAN via mDNS/DNS-SD instead of
+    /// requiring a hardcoded address, multicasting a query for
+    /// [`mdns::SERVICE_TYPE`] and collecting PTR/SRV/A answers for
+    /// `timeout`. Returns each advertised instance's service name paired
+    /// with the socket address to [`Self::connect`] to; an instance whose
+    /// SRV or A record never arrives in the window is left out.
+    pub fn discover(timeout: Duration) -> Result<Vec<(String, SocketAddr)>, Error> {
+        let sock = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        sock.join_multicast_v4(&mdns::MULTICAST_ADDR_V4, &Ipv4Addr::UNSPECIFIED)?;
+
+        let query = mdns::build_query();
+        sock.send_to(&query, (mdns::MULTICAST_ADDR_V4, mdns::MDNS_PORT))?;
+
+        let deadline = Instant::now() + timeout;
+        let mut buf = [0u8; 512];
+        let mut instances = Vec::new();
+        let mut srvs = HashMap::new();
+        let mut hosts = HashMap::new();
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            sock.set_read_timeout(Some(remaining))?;
+
+            let n = match sock.recv(&mut buf) {
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(Error::Io(e)),
+            };
+
+            let Ok(answers) = mdns::parse_answers(&buf[..n]) else {
+                continue;
+            };
+
+            for answer in answers {
+                match answer {
+                    mdns::Answer::Ptr { target, .. } => instances.push(target),
+                    mdns::Answer::Srv { name, port, target, .. } => {
+                        srvs.insert(name, (target, port));
+                    }
+                    mdns::Answer::A { name, addr, .. } => {
+                        hosts.insert(name, addr);
+                    }
+                }
+            }
+        }
+
+        let found = instances
+            .into_iter()
+            .filter_map(|instance| {
+                let (host, port) = srvs.get(&instance)?;
+                let addr = hosts.get(host)?;
+                Some((instance, SocketAddr::V4(SocketAddrV4::new(*addr, *port))))
+            })
+            .collect();
+
+        Ok(found)
+    }
+
     pub fn raw<'de, PRQ, PRS, RQ, RS, S>(
         &mut self,
         endpoint: S,
@@ -209,36 +372,46 @@ impl Client {
     {
         let key = Key::for_path::<PRQ>(endpoint.as_ref());
 
+        // Every logical request gets its own seq_no so a reply delayed past
+        // an earlier request's timeout can't be mistaken for this one's.
+        // Retransmits of *this* request reuse the same seq_no, since any of
+        // the copies reaching the server can produce the reply we want.
+        let seq_no = self.seq_no;
+        self.seq_no = self.seq_no.wrapping_add(1);
+
         let mut retry = 0;
         let p_payload = payload.into();
-        while retry <= self.retries {
-            let req = to_slice_keyed(0, key, &p_payload, buf)?;
-            self.sock.as_mut().ok_or(Error::NotConnected)?.send(req)?;
 
-            let resp = self.sock.as_mut().ok_or(Error::NotConnected)?.recv(buf);
-
-            if resp.is_ok() {
-                break;
-            }
+        'send: loop {
+            let req = to_slice_keyed(seq_no, key, &p_payload, buf)?;
+            self.sock.as_mut().ok_or(Error::NotConnected)?.send(req)?;
 
-            match resp.as_ref().unwrap_err().kind() {
-                io::ErrorKind::WouldBlock if retry < self.retries => {
-                    retry += 1;
-                    continue;
+            loop {
+                let resp = self.sock.as_mut().ok_or(Error::NotConnected)?.recv(buf);
+
+                match resp {
+                    Ok(_) => {}
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        if retry >= self.retries {
+                            return Err(Error::NoResponse((seq_no, key)));
+                        }
+                        retry += 1;
+                        continue 'send;
+                    }
+                    Err(e) => return Err(Error::Io(e)),
                 }
-                io::ErrorKind::WouldBlock if retry >= self.retries => {
-                    return Err(Error::NoResponse((0, key)))
+
+                let (hdr, rest) = extract_header_from_bytes(buf)?;
+                if hdr.seq_no == seq_no && hdr.key == key {
+                    let payload = from_bytes::<PRS>(rest)?;
+                    return Ok((payload.into(), retry));
                 }
-                _ => return Err(Error::Io(resp.unwrap_err())),
-            }
-        }
 
-        let (hdr, rest) = extract_header_from_bytes(buf)?;
-        if hdr.seq_no == 0 && hdr.key == key {
-            let payload = from_bytes::<PRS>(rest)?;
-            Ok((payload.into(), retry))
-        } else {
-            Err(Error::BadResponse((hdr.seq_no, hdr.key)))
+                // A stale/duplicate straggler, e.g. a late reply to a
+                // request we already gave up on. Keep reading without
+                // resending or spending a retry until a matching reply
+                // shows up or the read times out.
+            }
         }
     }
 }
@@ -1,17 +1,69 @@
 /// Inspired by: https://github.com/jasonpeacock/led-bargraph, tweaked for
 /// my purposes.
+use embedded_hal::blocking::delay::DelayMs;
 use embedded_hal::blocking::i2c::{Write, WriteRead};
 use std::error;
 use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_io::Timer;
+use async_lock::Mutex;
 
 #[allow(unused)]
-use ht16k33::{Display, DisplayData, LedLocation, Oscillator, COMMONS_SIZE, HT16K33, ROWS_SIZE};
+use ht16k33::{DisplayData, LedLocation, Oscillator, COMMONS_SIZE, HT16K33, ROWS_SIZE};
 
-pub use ht16k33::Dimming;
+pub use ht16k33::{Dimming, Display};
 pub use wb_notifier_proto::LedColor;
+use wb_notifier_proto::AbortReason;
 
 pub struct Bargraph<I2C> {
     drv: HT16K33<I2C>,
+    addr: u8,
+    zones: Zones,
+    /// Mirrors what's currently been pushed to the hardware, one byte
+    /// (the `LedColor` discriminant) per LED. `ht16k33::HT16K33` doesn't
+    /// expose its own display buffer for readback, so [`Self::get_state`]
+    /// serves this cache instead of the real one.
+    buffer: [u8; NUM_LEDS as usize],
+}
+
+/// Number of LEDs this bargraph exposes, i.e. valid `num`s are `0..NUM_LEDS`.
+pub const NUM_LEDS: u8 = 24;
+
+/// Color thresholds for [`Bargraph::display_value`], expressed as the
+/// fraction of `NUM_LEDS` lit at which the bar escalates to the next
+/// color. Defaults to 60% green / 85% yellow; anything above `yellow_max`
+/// is red.
+#[derive(Debug, Clone, Copy)]
+pub struct Zones {
+    pub green_max: f32,
+    pub yellow_max: f32,
+}
+
+impl Default for Zones {
+    fn default() -> Self {
+        Zones {
+            green_max: 0.60,
+            yellow_max: 0.85,
+        }
+    }
+}
+
+impl Zones {
+    /// Which color the LED at 0-indexed position `num` should be when
+    /// it's lit, based on how far into the bar it sits.
+    fn color_for(self, num: u8) -> LedColor {
+        let frac = f32::from(num + 1) / f32::from(NUM_LEDS);
+
+        if frac <= self.green_max {
+            LedColor::Green
+        } else if frac <= self.yellow_max {
+            LedColor::Yellow
+        } else {
+            LedColor::Red
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -40,6 +92,33 @@ where
 
 impl<E> error::Error for Error<E> where E: error::Error {}
 
+impl<E> Error<E>
+where
+    E: error::Error + 'static,
+{
+    /// Classifies a [`Self::Hal`] failure via [`crate::classify_abort`];
+    /// `None` for [`Self::OutOfRange`], which isn't a bus fault at all.
+    #[must_use]
+    pub fn abort_reason(&self) -> Option<AbortReason> {
+        match self {
+            Error::Hal(e) => Some(crate::classify_abort(e)),
+            Error::OutOfRange => None,
+        }
+    }
+}
+
+/// Converts a byte stored in [`Bargraph::buffer`] back into the
+/// [`LedColor`] it was cast from; anything [`Bargraph::set_led_no`]
+/// couldn't have actually written falls back to `Off`.
+fn color_from_byte(byte: u8) -> LedColor {
+    match byte {
+        b if b == LedColor::Green as u8 => LedColor::Green,
+        b if b == LedColor::Red as u8 => LedColor::Red,
+        b if b == LedColor::Yellow as u8 => LedColor::Yellow,
+        _ => LedColor::Off,
+    }
+}
+
 impl<I2C, E> Bargraph<I2C>
 where
     I2C: Write<Error = E> + WriteRead<Error = E>,
@@ -47,7 +126,17 @@ where
     pub fn new(i2c: I2C, addr: u8) -> Self {
         let drv = HT16K33::new(i2c, addr);
 
-        Bargraph { drv }
+        Bargraph {
+            drv,
+            addr,
+            zones: Zones::default(),
+            buffer: [LedColor::Off as u8; NUM_LEDS as usize],
+        }
+    }
+
+    /// Overrides the color thresholds [`Self::display_value`] uses.
+    pub fn set_zones(&mut self, zones: Zones) {
+        self.zones = zones;
     }
 
     pub fn initialize(&mut self) -> Result<(), Error<E>> {
@@ -57,17 +146,31 @@ where
         Ok(())
     }
 
-    pub fn set_led_no(&mut self, num: u8, color: LedColor) -> Result<(), Error<E>> {
-        if num > 23 {
-            return Err(Error::OutOfRange);
-        }
+    /// Re-points this `Bargraph` at a freshly (re)opened `i2c` handle and
+    /// re-runs [`Self::initialize`], e.g. after the bus was lost and
+    /// reopened following a HAL error.
+    pub fn reinit(&mut self, i2c: I2C) -> Result<(), Error<E>> {
+        self.drv = HT16K33::new(i2c, self.addr);
+        self.initialize()
+    }
 
-        // Row and column mappings found via trial and error.
+    /// Row and column mappings found via trial and error.
+    fn led_locations(num: u8) -> (LedLocation, LedLocation) {
         let row = if num >= 12 { num % 4 + 4 } else { num % 4 };
         let col = (num / 4) % 3;
 
-        let red_loc = LedLocation::new(row, col).unwrap();
-        let green_loc = LedLocation::new(row + 8, col).unwrap();
+        (
+            LedLocation::new(row, col).unwrap(),
+            LedLocation::new(row + 8, col).unwrap(),
+        )
+    }
+
+    pub fn set_led_no(&mut self, num: u8, color: LedColor) -> Result<(), Error<E>> {
+        if num >= NUM_LEDS {
+            return Err(Error::OutOfRange);
+        }
+
+        let (red_loc, green_loc) = Self::led_locations(num);
 
         self.drv.update_display_buffer(red_loc, false);
         self.drv.update_display_buffer(green_loc, false);
@@ -81,6 +184,124 @@ where
         }
 
         self.drv.write_display_buffer()?;
+        self.buffer[num as usize] = color as u8;
+
+        Ok(())
+    }
+
+    /// Turns every LED off in one display-buffer write, regardless of
+    /// what color each was last set to.
+    pub fn clear_all(&mut self) -> Result<(), Error<E>> {
+        for num in 0..NUM_LEDS {
+            let (red_loc, green_loc) = Self::led_locations(num);
+            self.drv.update_display_buffer(red_loc, false);
+            self.drv.update_display_buffer(green_loc, false);
+        }
+
+        self.drv.write_display_buffer()?;
+        self.buffer = [LedColor::Off as u8; NUM_LEDS as usize];
+
+        Ok(())
+    }
+
+    /// Renders `value` out of `range` as a filled bar across all
+    /// `NUM_LEDS`, coloring each lit LED green/yellow/red per
+    /// [`Self::set_zones`]. Updates every LED in one batched
+    /// `write_display_buffer` so the whole bar changes atomically instead
+    /// of LED-by-LED. `value > range` clamps to every LED lit and the
+    /// display blinking at 2 Hz to flag the overflow; otherwise the
+    /// display is left at a steady `ON`.
+    pub fn display_value(&mut self, value: u16, range: u16) -> Result<(), Error<E>> {
+        let overflow = range == 0 || value > range;
+        let lit = if overflow {
+            NUM_LEDS
+        } else {
+            let frac = f32::from(value) / f32::from(range);
+            (frac * f32::from(NUM_LEDS)).ceil() as u8
+        };
+
+        for num in 0..NUM_LEDS {
+            let (red_loc, green_loc) = Self::led_locations(num);
+            self.drv.update_display_buffer(red_loc, false);
+            self.drv.update_display_buffer(green_loc, false);
+
+            let color = if num < lit {
+                self.zones.color_for(num)
+            } else {
+                LedColor::Off
+            };
+
+            if color == LedColor::Red || color == LedColor::Yellow {
+                self.drv.update_display_buffer(red_loc, true);
+            }
+
+            if color == LedColor::Green || color == LedColor::Yellow {
+                self.drv.update_display_buffer(green_loc, true);
+            }
+
+            self.buffer[num as usize] = color as u8;
+        }
+
+        self.drv.write_display_buffer()?;
+        self.set_display(if overflow { Display::TWO_HZ } else { Display::ON })?;
+
+        Ok(())
+    }
+
+    /// The display buffer [`Self::set_led_no`]/[`Self::clear_all`]/
+    /// [`Self::display_value`] have most recently pushed to the hardware,
+    /// one byte per LED. Lets a caller inspect what the panel should
+    /// currently be showing, e.g. to restore it after [`Self::self_test`].
+    #[must_use]
+    pub fn get_state(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// The color LED `num` is currently showing, decoded from
+    /// [`Self::get_state`]; `None` if `num` is out of range.
+    #[must_use]
+    pub fn led_color(&self, num: u8) -> Option<LedColor> {
+        self.buffer.get(num as usize).copied().map(color_from_byte)
+    }
+
+    /// Exercises every LED in turn, cycles the dimming and blink-rate
+    /// levels, and restores the buffer [`Self::get_state`] had beforehand.
+    /// Modeled on the "inspect state, then self-test before trusting the
+    /// device" pattern firmware updaters use (read back state, run the
+    /// test, restore it before marking the device booted), so a freshly
+    /// (re)connected panel can be validated without clobbering whatever it
+    /// was already showing.
+    pub fn self_test<D>(&mut self, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u16>,
+    {
+        let prior = self.buffer;
+
+        for num in 0..NUM_LEDS {
+            for color in [LedColor::Red, LedColor::Green, LedColor::Yellow, LedColor::Off] {
+                self.set_led_no(num, color)?;
+                delay.delay_ms(20);
+            }
+        }
+
+        for dim in [
+            Dimming::BRIGHTNESS_4_16,
+            Dimming::BRIGHTNESS_8_16,
+            Dimming::BRIGHTNESS_12_16,
+            Dimming::BRIGHTNESS_16_16,
+        ] {
+            self.set_dimming(dim)?;
+            delay.delay_ms(200);
+        }
+
+        for disp in [Display::TWO_HZ, Display::ONE_HZ, Display::HALF_HZ, Display::ON] {
+            self.set_display(disp)?;
+            delay.delay_ms(200);
+        }
+
+        for (num, byte) in prior.into_iter().enumerate() {
+            self.set_led_no(num as u8, color_from_byte(byte))?;
+        }
 
         Ok(())
     }
@@ -102,3 +323,91 @@ where
         self.drv.destroy()
     }
 }
+
+impl<I2C, E> Bargraph<I2C>
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + 'static,
+{
+    /// Runs `f` against the locked bargraph on a blocking-pool thread
+    /// instead of the caller's executor, so a slow HAL transaction can't
+    /// stall `blink`'s timer or the socket `recv_from` loop. Every
+    /// `*_async` method below is a thin wrapper around this; it's the one
+    /// place the lock-and-offload glue lives instead of being repeated at
+    /// every call site.
+    async fn with_blocking<F, T>(this: Arc<Mutex<Self>>, f: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        blocking::unblock(move || {
+            let mut guard = this.lock_arc_blocking();
+            f(&mut guard)
+        })
+        .await
+    }
+
+    /// Async counterpart to [`Self::set_led_no`], for a caller sharing
+    /// this bargraph with other concurrently-running tasks.
+    pub async fn set_led_no_async(
+        this: Arc<Mutex<Self>>,
+        num: u8,
+        color: LedColor,
+    ) -> Result<(), Error<E>> {
+        Self::with_blocking(this, move |bg| bg.set_led_no(num, color)).await
+    }
+
+    /// Async counterpart to [`Self::clear_all`].
+    pub async fn clear_all_async(this: Arc<Mutex<Self>>) -> Result<(), Error<E>> {
+        Self::with_blocking(this, Self::clear_all).await
+    }
+
+    /// Async counterpart to [`Self::set_dimming`].
+    pub async fn set_dimming_async(this: Arc<Mutex<Self>>, dim: Dimming) -> Result<(), Error<E>> {
+        Self::with_blocking(this, move |bg| bg.set_dimming(dim)).await
+    }
+
+    /// Async counterpart to [`Self::set_display`].
+    pub async fn set_display_async(this: Arc<Mutex<Self>>, disp: Display) -> Result<(), Error<E>> {
+        Self::with_blocking(this, move |bg| bg.set_display(disp)).await
+    }
+
+    /// Async counterpart to [`Self::self_test`], but taking the lock once
+    /// per step instead of for the whole ~3.5s sequence: [`Self::self_test`]
+    /// runs entirely inside one `with_blocking` offload, so a concurrent
+    /// `notify`/`ack`/blink-rate change on this same bargraph would queue
+    /// behind the entire test instead of interleaving with it. This drives
+    /// the same steps through the other `*_async` methods above, releasing
+    /// the lock between each one so those callers aren't starved.
+    pub async fn self_test_async(this: Arc<Mutex<Self>>) -> Result<(), Error<E>> {
+        let prior = Self::with_blocking(this.clone(), |bg| bg.buffer).await;
+
+        for num in 0..NUM_LEDS {
+            for color in [LedColor::Red, LedColor::Green, LedColor::Yellow, LedColor::Off] {
+                Self::set_led_no_async(this.clone(), num, color).await?;
+                Timer::after(Duration::from_millis(20)).await;
+            }
+        }
+
+        for dim in [
+            Dimming::BRIGHTNESS_4_16,
+            Dimming::BRIGHTNESS_8_16,
+            Dimming::BRIGHTNESS_12_16,
+            Dimming::BRIGHTNESS_16_16,
+        ] {
+            Self::set_dimming_async(this.clone(), dim).await?;
+            Timer::after(Duration::from_millis(200)).await;
+        }
+
+        for disp in [Display::TWO_HZ, Display::ONE_HZ, Display::HALF_HZ, Display::ON] {
+            Self::set_display_async(this.clone(), disp).await?;
+            Timer::after(Duration::from_millis(200)).await;
+        }
+
+        for (num, byte) in prior.into_iter().enumerate() {
+            Self::set_led_no_async(this.clone(), num as u8, color_from_byte(byte)).await?;
+        }
+
+        Ok(())
+    }
+}
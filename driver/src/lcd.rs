@@ -1,6 +1,8 @@
 use std::{error, fmt};
 use std::cell::RefCell;
+use std::sync::Arc;
 
+use async_lock::Mutex;
 use embedded_hal::blocking::i2c::Write as I2cWrite;
 use embedded_hal::blocking::delay::{DelayMs, DelayUs};
 use hd44780_driver::bus::I2CMCP23008Bus;
@@ -8,26 +10,36 @@ use hd44780_driver::{Cursor, CursorBlink, Display, DisplayMode, HD44780};
 use hd44780_driver::display_size::DisplaySize;
 
 pub use wb_notifier_proto::SetBacklight;
+use wb_notifier_proto::{AbortReason, MsgStatus};
+
+/// Columns on the attached HD44780 display.
+pub const LCD_COLS: u8 = 20;
+/// Rows on the attached HD44780 display.
+pub const LCD_ROWS: u8 = 4;
 
 pub struct Lcd<I2C, D> where I2C: I2cWrite {
     drv: HD44780<I2CMCP23008Bus<I2C>>,
     delay: D,
     pos: u8,
+    addr: u8,
 }
 
-struct Msg(u8, String);
-
 #[derive(Debug, Clone)]
 pub enum Error {
     InitMcp,
     Init,
-    Busy(u8),
     SetCursorPos,
     WriteStr,
     Clear,
-    SetBacklight,
-    /// Non-fatal error that indicates the driver yielded voluntarily.
-    Yielded(u8)
+    /// Unlike the other I2C-touching variants above, this one talks to
+    /// the bus directly rather than through `hd44780_driver`'s own
+    /// (unclassifiable) error type, so the underlying I2C failure can
+    /// actually be classified.
+    SetBacklight(AbortReason),
+    /// `write_msg`'s message contained a character outside the HD44780's
+    /// built-in ASCII character set; writing it anyway would have just
+    /// silently dropped it.
+    NonAscii,
 }
 
 impl fmt::Display for Error
@@ -37,33 +49,34 @@ impl fmt::Display for Error
             Error::InitMcp => write!(f, "I2C expander initialization error"),
             Error::Init => write!(f, "LCD initialization error"),
             Error::SetCursorPos => write!(f, "could not set cursor pos"),
-            Error::Busy(id) => write!(f, "driver busy writing msg id {id}"),
             Error::WriteStr => write!(f, "could not write string"),
             Error::Clear => write!(f, "could not clear display"),
-            Error::SetBacklight => write!(f, "could not control backlight"),
-            Error::Yielded(pos) => write!(f, "driver voluntarily yielded pos {pos}"),
+            Error::SetBacklight(reason) => write!(f, "could not control backlight: {reason}"),
+            Error::NonAscii => write!(f, "message contains a non-ASCII character"),
         }
     }
 }
 
 impl error::Error for Error {}
 
-enum LineFsm {
-    Idle,
-    One,
-    Two,
-    Three,
-    Four
-}
-
 impl<I2C, D, E> Lcd<I2C, D>
 where
-    I2C: I2cWrite<Error = E>, D: DelayMs<u8> + DelayUs<u16> 
+    I2C: I2cWrite<Error = E>, D: DelayMs<u8> + DelayUs<u16>,
+    E: std::error::Error + 'static,
 {
     pub fn new(i2c: I2C, mut delay: D, addr: u8) -> Result<Self, Error> {
         let drv = HD44780::new_i2c_mcp23008(i2c, addr, true, &mut delay).map_err(|_| Error::InitMcp)?;
 
-        Ok(Lcd { drv, delay, pos: 0 })
+        Ok(Lcd { drv, delay, pos: 0, addr })
+    }
+
+    /// Re-points this `Lcd` at a freshly (re)opened `i2c` handle and
+    /// re-runs [`Self::initialize`], e.g. after the bus was lost and
+    /// reopened following a HAL error.
+    pub fn reinit(&mut self, i2c: I2C) -> Result<(), Error> {
+        self.drv = HD44780::new_i2c_mcp23008(i2c, self.addr, true, &mut self.delay)
+            .map_err(|_| Error::InitMcp)?;
+        self.initialize()
     }
 
     pub fn initialize(&mut self) -> Result<(), Error> {
@@ -73,35 +86,128 @@ where
             DisplayMode { display: Display::On, cursor_visibility: Cursor::Visible, cursor_blink: CursorBlink::On },
             &mut self.delay
         ).map_err(|_| Error::Init)?;
-        self.drv.set_display_size(DisplaySize::new(20, 4));
+        self.drv.set_display_size(DisplaySize::new(LCD_COLS, LCD_ROWS));
 
         Ok(())
     }
 
     pub fn set_backlight(&mut self, back: SetBacklight) -> Result<(), Error> {
         match back {
-            SetBacklight::Off => self.drv.get_mut().set_backlight(false).map_err(|_| Error::SetBacklight)?,
-            SetBacklight::On => self.drv.get_mut().set_backlight(true).map_err(|_| Error::SetBacklight)?
+            SetBacklight::Off => self
+                .drv
+                .get_mut()
+                .set_backlight(false)
+                .map_err(|e| Error::SetBacklight(crate::classify_abort(&e)))?,
+            SetBacklight::On => self
+                .drv
+                .get_mut()
+                .set_backlight(true)
+                .map_err(|e| Error::SetBacklight(crate::classify_abort(&e)))?,
         }
 
         Ok(())
     }
 
-    pub fn write_msg(&mut self, /* id: u8, */ msg: String)  -> Result<u8, Error> {
+    /// Clears the display and writes `msg` across all four rows,
+    /// wrapping at [`LCD_COLS`]. Rejects a non-ASCII message outright
+    /// rather than silently dropping the characters that don't fit the
+    /// HD44780's built-in character set, and reports back whether the
+    /// message was too long for the `LCD_COLS` * `LCD_ROWS` display
+    /// instead of silently truncating it.
+    pub fn write_msg(&mut self, msg: &str) -> Result<MsgStatus, Error> {
+        if !msg.is_ascii() {
+            return Err(Error::NonAscii);
+        }
+
         self.drv.clear(&mut self.delay).map_err(|_| Error::Clear)?;
         self.drv.set_cursor_pos(0, &mut self.delay).map_err(|_| Error::SetCursorPos)?;
         self.pos = 0;
 
+        let capacity = LCD_COLS as usize * LCD_ROWS as usize;
+        let mut status = MsgStatus::Ok;
+
         for (i, c) in msg.chars().enumerate() {
-            if i % 20 == 0 && i != 0 {
-                self.drv.set_cursor_pos(i as u8, &mut self.delay).map_err(|_| Error::SetCursorPos)?;
+            if i >= capacity {
+                status = MsgStatus::Truncated;
+                break;
             }
 
-            if c.is_ascii() {
-                self.drv.write_byte(c as u8, &mut self.delay).map_err(|_| Error::WriteStr)?;
+            if i % LCD_COLS as usize == 0 && i != 0 {
+                self.drv.set_cursor_pos(i as u8, &mut self.delay).map_err(|_| Error::SetCursorPos)?;
             }
+
+            self.drv.write_byte(c as u8, &mut self.delay).map_err(|_| Error::WriteStr)?;
+        }
+
+        Ok(status)
+    }
+
+    /// Writes `text` to `row` (0-indexed), left-justified and padded with
+    /// spaces out to [`LCD_COLS`] so it fully overwrites whatever was on
+    /// that row before. Unlike [`Self::write_msg`], only touches `row`.
+    pub fn write_line(&mut self, row: u8, text: &str) -> Result<(), Error> {
+        let pos = row * LCD_COLS;
+        self.drv
+            .set_cursor_pos(pos, &mut self.delay)
+            .map_err(|_| Error::SetCursorPos)?;
+
+        let padded = text
+            .chars()
+            .filter(char::is_ascii)
+            .chain(std::iter::repeat(' '))
+            .take(LCD_COLS as usize);
+        for c in padded {
+            self.drv.write_byte(c as u8, &mut self.delay).map_err(|_| Error::WriteStr)?;
         }
 
-        Ok(0)
+        Ok(())
+    }
+
+}
+
+impl<I2C, D, E> Lcd<I2C, D>
+where
+    I2C: Send + I2cWrite<Error = E> + 'static,
+    D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+    E: Send + 'static,
+{
+    /// Runs `f` against the locked display on a blocking-pool thread
+    /// instead of the caller's executor, so a slow write can't stall
+    /// `marquee`'s scroll timer or the socket `recv_from` loop.
+    async fn with_blocking<F, T>(this: Arc<Mutex<Self>>, f: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        blocking::unblock(move || {
+            let mut guard = this.lock_arc_blocking();
+            f(&mut guard)
+        })
+        .await
+    }
+
+    /// Async counterpart to [`Self::write_line`].
+    pub async fn write_line_async(
+        this: Arc<Mutex<Self>>,
+        row: u8,
+        text: String,
+    ) -> Result<(), Error> {
+        Self::with_blocking(this, move |lcd| lcd.write_line(row, &text)).await
+    }
+
+    /// Async counterpart to [`Self::set_backlight`].
+    pub async fn set_backlight_async(
+        this: Arc<Mutex<Self>>,
+        back: SetBacklight,
+    ) -> Result<(), Error> {
+        Self::with_blocking(this, move |lcd| lcd.set_backlight(back)).await
+    }
+
+    /// Async counterpart to [`Self::write_msg`].
+    pub async fn write_msg_async(
+        this: Arc<Mutex<Self>>,
+        msg: String,
+    ) -> Result<MsgStatus, Error> {
+        Self::with_blocking(this, move |lcd| lcd.write_msg(&msg)).await
     }
 }
@@ -1,20 +1,59 @@
 pub mod bargraph;
 pub mod lcd;
 
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::sync::Arc;
 
 use async_lock::Mutex;
 use embedded_hal::blocking::i2c::{Write, WriteRead};
+use wb_notifier_proto::AbortReason;
 
-pub struct Sensors<'s, I2C, D>
+/// Walks `err`'s [`std::error::Error::source`] chain looking for the
+/// underlying [`std::io::Error`] a Linux I2C HAL wraps, and classifies its
+/// errno into an [`AbortReason`]. Falls back to `Other` if no `io::Error`
+/// turns up in the chain (e.g. a non-Linux HAL) or its errno isn't one we
+/// recognize.
+#[must_use]
+pub fn classify_abort<E>(err: &E) -> AbortReason
+where
+    E: std::error::Error + 'static,
+{
+    let mut cause: Option<&(dyn std::error::Error + 'static)> = Some(err);
+
+    while let Some(e) = cause {
+        if let Some(io_err) = e.downcast_ref::<std::io::Error>() {
+            return match io_err.raw_os_error() {
+                // ENXIO / EREMOTEIO: nothing acknowledged the address.
+                Some(6) | Some(121) => AbortReason::NoAcknowledge,
+                // EAGAIN: i2c-dev's ioctl retry signal, the closest Linux
+                // gets to reporting arbitration loss.
+                Some(11) => AbortReason::ArbitrationLoss,
+                _ => AbortReason::Other,
+            };
+        }
+
+        cause = e.source();
+    }
+
+    AbortReason::Other
+}
+
+/// A device handle that can come and go at runtime: `None` until a
+/// matching `Device` is configured, and still reachable from every
+/// in-flight `Sensors` copy once it is, since they all share the same
+/// `Rc<RefCell<_>>` slot rather than a snapshot taken at startup.
+pub type Slot<T> = Rc<RefCell<Option<Arc<Mutex<T>>>>>;
+
+pub struct Sensors<I2C, D>
 where
     I2C: Write + WriteRead,
 {
-    pub bargraph: Option<&'s Arc<Mutex<bargraph::Bargraph<I2C>>>>,
-    pub lcd: Option<&'s Arc<Mutex<lcd::Lcd<I2C, D>>>>,
+    pub bargraph: Option<Slot<bargraph::Bargraph<I2C>>>,
+    pub lcd: Option<Slot<lcd::Lcd<I2C, D>>>,
 }
 
-impl<'s, I2C, D> Default for Sensors<'s, I2C, D>
+impl<I2C, D> Default for Sensors<I2C, D>
 where
     I2C: Write + WriteRead,
 {
@@ -23,7 +62,7 @@ where
     }
 }
 
-impl<'s, I2C, D> Sensors<'s, I2C, D>
+impl<I2C, D> Sensors<I2C, D>
 where
     I2C: Write + WriteRead,
 {
@@ -34,4 +73,20 @@ where
             lcd: None,
         }
     }
+
+    /// Clones out whatever handle the bargraph slot currently holds, if a
+    /// bargraph has been configured at all. Unlike reading the field
+    /// directly, this sees a device hot-added after `Sensors` itself was
+    /// built, since `bargraph` just points at the shared slot rather than
+    /// owning a snapshot of it.
+    #[must_use]
+    pub fn bargraph(&self) -> Option<Arc<Mutex<bargraph::Bargraph<I2C>>>> {
+        self.bargraph.as_ref().and_then(|slot| slot.borrow().clone())
+    }
+
+    /// Same as [`Sensors::bargraph`], for the LCD.
+    #[must_use]
+    pub fn lcd(&self) -> Option<Arc<Mutex<lcd::Lcd<I2C, D>>>> {
+        self.lcd.as_ref().and_then(|slot| slot.borrow().clone())
+    }
 }
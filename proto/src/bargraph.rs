@@ -4,6 +4,8 @@ pub const SET_LED_PATH: &str = "led/set";
 pub const NOTIFY_PATH: &str = "led/notify";
 pub const CLEAR_NOTIFY_PATH: &str = "led/ack";
 pub const SET_DIMMING_PATH: &str = "led/dimming";
+pub const SET_BLINK_THRESHOLDS_PATH: &str = "led/blink_thresholds";
+pub const SELF_TEST_PATH: &str = "led/selftest";
 
 #[derive(Serialize, Deserialize, Schema)]
 pub struct SetLed {
@@ -13,7 +15,7 @@ pub struct SetLed {
 
 // This is our Response type
 #[derive(Serialize, Deserialize, Schema)]
-pub struct SetLedResponse(pub Result<(), ()>);
+pub struct SetLedResponse(pub Result<(), DeviceError>);
 
 #[derive(Serialize, Deserialize, Schema, Clone, Copy, Debug, PartialEq)]
 /// LED colors.
@@ -35,18 +37,22 @@ pub enum SetDimming {
 }
 
 #[derive(Serialize, Deserialize, Schema)]
-pub struct SetDimmingResponse(pub Result<(), ()>);
+pub struct SetDimmingResponse(pub Result<(), DeviceError>);
 
-#[derive(Serialize, Deserialize, Schema)]
+#[derive(Serialize, Deserialize, Schema, Clone, Debug)]
 pub struct Notify {
     pub num: u8,
     pub status: Status,
+    /// Human-readable text to show on the LCD row `num` maps to, if one
+    /// is configured. The daemon scrolls it as a marquee when it's too
+    /// long to fit on one row.
+    pub msg: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Schema)]
-pub struct NotifyResponse(pub Result<(), ()>);
+pub struct NotifyResponse(pub Result<(), DeviceError>);
 
-#[derive(Serialize, Deserialize, Schema, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Schema, PartialEq, Debug, Clone, Copy)]
 pub enum Status {
     Ok,
     Warning,
@@ -55,9 +61,30 @@ pub enum Status {
 
 #[derive(Serialize, Deserialize, Schema)]
 pub struct Ack {
-    pub num: u8,
-    pub status: Status,
+    /// The LED to ack, or `None` to ack (clear) every LED at once.
+    pub num: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct AckResponse(pub Result<(), DeviceError>);
+
+/// How long the per-LED escalation backed by the oldest unacked
+/// notification dwells at each blink rate before dropping to the next one.
+/// `fast_to_medium_secs` bounds the 2 Hz stage, `medium_to_slow_secs` the
+/// 1 Hz stage; the daemon then stays at 0.5 Hz until the LED is acked.
+#[derive(Serialize, Deserialize, Schema, Clone, Copy, Debug)]
+pub struct SetBlinkThresholds {
+    pub fast_to_medium_secs: u32,
+    pub medium_to_slow_secs: u32,
 }
 
 #[derive(Serialize, Deserialize, Schema)]
-pub struct AckResponse(pub Result<(), ()>);
+pub struct SetBlinkThresholdsResponse(pub Result<(), RequestError>);
+
+/// Runs the bargraph's self-test, validating it before trusting its
+/// output and restoring whatever it was showing beforehand.
+#[derive(Serialize, Deserialize, Schema)]
+pub struct SelfTest {}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct SelfTestResponse(pub Result<(), DeviceError>);
@@ -0,0 +1,66 @@
+use super::*;
+
+pub const LIST_DEVICES_PATH: &str = "config/device/list";
+pub const ADD_DEVICE_PATH: &str = "config/device/add";
+pub const REMOVE_DEVICE_PATH: &str = "config/device/remove";
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct ListDevices {}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct ListDevicesResponse(pub Vec<Device>);
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct AddDevice(pub Device);
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct AddDeviceResponse(pub Result<(), ConfigError>);
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct RemoveDevice {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+pub struct RemoveDeviceResponse(pub Result<(), ConfigError>);
+
+/// What went wrong servicing one of the `config/device/*` endpoints, kept
+/// separate from [`DeviceError`] since these are about the persisted
+/// device list itself rather than a single in-flight driver call.
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub enum ConfigError {
+    /// A device with this name is already configured.
+    Duplicate,
+    /// No device with this name is configured, or the server keeps no
+    /// device config at all.
+    NotFound,
+    /// A device of this `Driver` is already active; only one `Bargraph`
+    /// and one `Hd44780` can be brought up at a time, since each has a
+    /// single shared slot.
+    DriverBusy,
+    /// The device couldn't be brought up, and so wasn't added to the
+    /// config; or it was brought up, but writing the config to disk failed.
+    Init(DeviceError),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Duplicate => write!(f, "a device with this name is already configured"),
+            ConfigError::NotFound => write!(f, "no device with this name is configured"),
+            ConfigError::DriverBusy => {
+                write!(f, "a device of this driver is already active")
+            }
+            ConfigError::Init(e) => write!(f, "device failed to initialize or to persist: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Duplicate | ConfigError::NotFound | ConfigError::DriverBusy => None,
+            ConfigError::Init(e) => Some(e),
+        }
+    }
+}
@@ -3,21 +3,70 @@ use super::*;
 use std::error;
 use std::fmt;
 
-/* TODO: "Get Error" endpoint... something like
-#[derive(Serialize, Deserialize, Schema)]
+use postcard_rpc::Key;
+
+pub const ERROR_QUERY_PATH: &str = "debug/error";
+
+/// Looks up what became of a previously-sent `(seq_no, key)` request.
+/// Exists because some failures (the endpoint didn't exist, the frame
+/// didn't even parse) happen before any per-endpoint response type gets a
+/// chance to carry the reason, so there's otherwise no way for a client to
+/// learn more than "it didn't work".
+#[derive(Debug, Serialize, Deserialize, Schema)]
 pub struct ErrorQuery {
     pub seq_no: u32,
     pub key: Key,
 }
 
-#[derive(Serialize, Deserialize, Schema)]
+#[derive(Debug, Serialize, Deserialize, Schema)]
 pub struct LastErrorResponse(pub Option<DispatchError>);
 
-#[derive(Serialize, Deserialize, Schema)]
+/// A coarse, wire-safe classification of why a request failed, for
+/// [`ErrorQuery`] to hand back. Request-specific responses (e.g.
+/// `SetLedResponse`) still carry the precise [`DeviceError`]/[`ConfigError`]
+/// when the request reaches a handler at all; this exists for the cases
+/// that never make it that far.
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
 pub enum DispatchError {
-    NonexistentEndpoint
+    /// `(seq_no, key)` didn't match any registered endpoint.
+    NonexistentEndpoint,
+    /// The frame didn't decode as the endpoint's expected request type.
+    Malformed,
+    /// The request reached a handler, but the device/driver rejected it.
+    Device(DeviceError),
+    /// The request reached a handler, but the persisted device config
+    /// rejected it.
+    Config(ConfigError),
+    /// The request reached a handler, but the schedule it asked for was
+    /// rejected.
+    Schedule(ScheduleError),
+    /// Anything else: I/O, transport, or other server-side failure.
+    Other,
+}
+
+impl fmt::Display for DispatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NonexistentEndpoint => write!(f, "no handler registered for this request"),
+            Self::Malformed => write!(f, "request did not decode as the endpoint's type"),
+            Self::Device(e) => write!(f, "{e}"),
+            Self::Config(e) => write!(f, "{e}"),
+            Self::Schedule(e) => write!(f, "{e}"),
+            Self::Other => write!(f, "server-side error, see server logs"),
+        }
+    }
+}
+
+impl error::Error for DispatchError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::NonexistentEndpoint | Self::Malformed | Self::Other => None,
+            Self::Device(e) => Some(e),
+            Self::Config(e) => Some(e),
+            Self::Schedule(e) => Some(e),
+        }
+    }
 }
-*/
 
 #[derive(Debug, Serialize, Deserialize, Schema)]
 pub struct RequestError {}
@@ -32,3 +81,60 @@ impl fmt::Display for RequestError {
 }
 
 impl error::Error for RequestError {}
+
+/// Coarse, HAL-independent classification of why an I2C transaction
+/// failed. Lets a client tell "nothing answered at that address" (wrong
+/// address, or the device simply isn't plugged in) apart from "something
+/// else is wrong with the bus" (wiring, contention, power), instead of
+/// every failure looking like the same opaque bus error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Schema)]
+pub enum AbortReason {
+    /// The target address never acknowledged. Most often means no device
+    /// is present at that address.
+    NoAcknowledge,
+    /// The bus was lost to another controller mid-transaction.
+    ArbitrationLoss,
+    /// Anything else: I/O error, timeout, or a HAL that can't tell us
+    /// more.
+    Other,
+}
+
+impl fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoAcknowledge => write!(f, "no device acknowledged the address"),
+            Self::ArbitrationLoss => write!(f, "lost bus arbitration"),
+            Self::Other => write!(f, "other I2C bus error"),
+        }
+    }
+}
+
+/// What actually went wrong servicing a device request, in place of the
+/// unit `RequestError {}` that made every failure look the same to a
+/// client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Schema)]
+pub enum DeviceError {
+    /// `num` is outside `0..=max` for this device.
+    OutOfRange { num: u8, max: u8 },
+    /// The I2C transaction with the device failed.
+    I2cBus(AbortReason),
+    /// The device hasn't been initialized yet.
+    NotInitialized,
+    /// This device doesn't support the requested operation.
+    Unsupported,
+}
+
+impl fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfRange { num, max } => {
+                write!(f, "{num} is out of range, expected 0..={max}")
+            }
+            Self::I2cBus(reason) => write!(f, "I2C bus error: {reason}"),
+            Self::NotInitialized => write!(f, "device not initialized"),
+            Self::Unsupported => write!(f, "device does not support this operation"),
+        }
+    }
+}
+
+impl error::Error for DeviceError {}
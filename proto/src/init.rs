@@ -2,16 +2,16 @@ pub use super::*;
 
 #[derive(Debug, Serialize, Deserialize, Schema, Hash, Clone)]
 pub struct Device {
-    // TODO: In principle, we could have dynamic endpoints and distinguish multiple
-    // of the same devices by this name String. But right now, it does nothing.
+    /// Identifies this device in the `config/device/*` endpoints; the
+    /// persisted device list is keyed on this rather than `addr`, since
+    /// `addr` alone can't tell two same-driver devices apart.
     pub name: String,
     pub addr: u8,
     pub driver: Driver,
 }
 
-// TODO: Parameterize based on an InitFailure type?
 #[derive(Serialize, Deserialize, Schema)]
-pub struct InitResponse<E>(pub Result<(), E>);
+pub struct InitResponse(pub Result<(), DeviceError>);
 
 #[derive(Debug, Serialize, Deserialize, Schema, Hash, Clone)]
 pub enum Driver {
@@ -11,17 +11,17 @@ pub struct Enable();
 #[derive(Serialize, Deserialize, Schema)]
 pub struct EnableResponse(Result<(), RequestError>);
 
-#[derive(Serialize, Deserialize, Schema, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Schema, PartialEq, Debug, Clone, Copy)]
 pub enum SetBacklight {
     On,
     Off,
 }
 
 #[derive(Serialize, Deserialize, Schema)]
-pub struct SetBacklightResponse(pub Result<(), RequestError>);
+pub struct SetBacklightResponse(pub Result<(), DeviceError>);
 
-impl From<Result<(), RequestError>> for SetBacklightResponse {
-    fn from(value: Result<(), RequestError>) -> Self {
+impl From<Result<(), DeviceError>> for SetBacklightResponse {
+    fn from(value: Result<(), DeviceError>) -> Self {
         Self(value)
     }
 }
@@ -2,13 +2,18 @@ use postcard::experimental::schema::Schema;
 use serde::{Deserialize, Serialize};
 
 mod bargraph;
+mod config;
 mod echo;
 mod error;
 mod init;
 mod lcd;
+pub mod mdns;
+mod schedule;
 
 pub use bargraph::*;
+pub use config::*;
 pub use echo::*;
 pub use error::*;
 pub use init::*;
 pub use lcd::*;
+pub use schedule::*;
@@ -0,0 +1,285 @@
+//! Hand-rolled DNS message encode/decode for mDNS/DNS-SD service discovery,
+//! shared between [`crate`]'s consumers (`wb-notifier-client`'s discovery
+//! and the server's advertiser). Only the record types DNS-SD actually
+//! needs (PTR/SRV/A) are supported; this isn't a general resolver. Modeled
+//! on the compact, no-std-friendly DNS record handling embassy-net's
+//! network stack uses rather than pulling in a full resolver crate.
+use std::error;
+use std::fmt;
+use std::net::Ipv4Addr;
+
+/// DNS-SD service type this daemon advertises/discovers under.
+pub const SERVICE_TYPE: &str = "_wbnotifier._udp.local.";
+/// Standard mDNS port and IPv4 multicast group.
+pub const MDNS_PORT: u16 = 5353;
+pub const MULTICAST_ADDR_V4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+/// High bit of the rrclass field, set on responses for records this
+/// advertiser is the sole owner of (RFC 6762 unique records).
+const CLASS_CACHE_FLUSH: u16 = 0x8000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Answer {
+    Ptr {
+        ttl: u32,
+        /// The service instance this PTR resolves the service type to,
+        /// e.g. `"bench1._wbnotifier._udp.local."`.
+        target: String,
+    },
+    Srv {
+        /// The service instance name this record answers for (matches a
+        /// [`Answer::Ptr::target`]).
+        name: String,
+        ttl: u32,
+        port: u16,
+        /// Host name to resolve via an [`Answer::A`] with a matching
+        /// `name`, e.g. `"bench1.local."`.
+        target: String,
+    },
+    A {
+        /// The host name this record answers for (matches an
+        /// [`Answer::Srv::target`]).
+        name: String,
+        ttl: u32,
+        addr: Ipv4Addr,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    Truncated,
+    BadName,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Truncated => write!(f, "DNS message ended before expected"),
+            Error::BadName => write!(f, "malformed DNS name"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+fn encode_name(name: &str, out: &mut Vec<u8>) {
+    for label in name.trim_end_matches('.').split('.') {
+        out.push(label.len() as u8);
+        out.extend_from_slice(label.as_bytes());
+    }
+    out.push(0);
+}
+
+/// Reads a (possibly pointer-compressed) name starting at `pos`, returning
+/// the decoded name and the offset just past it in the *original* message
+/// (i.e. not following into a pointer's target).
+fn decode_name(buf: &[u8], mut pos: usize) -> Result<(String, usize), Error> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    let mut hops = 0;
+
+    loop {
+        let len = *buf.get(pos).ok_or(Error::Truncated)? as usize;
+
+        if len == 0 {
+            if end.is_none() {
+                end = Some(pos + 1);
+            }
+            break;
+        }
+
+        if len & 0xc0 == 0xc0 {
+            let hi = len & !0xc0;
+            let lo = *buf.get(pos + 1).ok_or(Error::Truncated)? as usize;
+
+            if end.is_none() {
+                end = Some(pos + 2);
+            }
+
+            hops += 1;
+            if hops > 16 {
+                return Err(Error::BadName);
+            }
+
+            pos = (hi << 8) | lo;
+            continue;
+        }
+
+        let label_start = pos + 1;
+        let label_end = label_start + len;
+        let label = buf.get(label_start..label_end).ok_or(Error::Truncated)?;
+        labels.push(std::str::from_utf8(label).map_err(|_| Error::BadName)?.to_string());
+        pos = label_end;
+    }
+
+    labels.push(String::new());
+    Ok((labels.join("."), end.unwrap_or(pos)))
+}
+
+fn read_u16(buf: &[u8], pos: usize) -> Result<u16, Error> {
+    buf.get(pos..pos + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or(Error::Truncated)
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Result<u32, Error> {
+    buf.get(pos..pos + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(Error::Truncated)
+}
+
+/// Builds a standard (non-multicast-specific) PTR query for `SERVICE_TYPE`,
+/// suitable for sending to [`MULTICAST_ADDR_V4`]`:`[`MDNS_PORT`].
+#[must_use]
+pub fn build_query() -> Vec<u8> {
+    let mut out = Vec::with_capacity(32);
+    out.extend_from_slice(&0u16.to_be_bytes()); // id
+    out.extend_from_slice(&0u16.to_be_bytes()); // flags: standard query
+    out.extend_from_slice(&1u16.to_be_bytes()); // qdcount
+    out.extend_from_slice(&0u16.to_be_bytes()); // ancount
+    out.extend_from_slice(&0u16.to_be_bytes()); // nscount
+    out.extend_from_slice(&0u16.to_be_bytes()); // arcount
+
+    encode_name(SERVICE_TYPE, &mut out);
+    out.extend_from_slice(&TYPE_PTR.to_be_bytes());
+    out.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    out
+}
+
+/// Builds an mDNS response announcing one service instance: a PTR from
+/// `SERVICE_TYPE` to `instance`, a SRV from `instance` to `host`:`port`,
+/// and an A from `host` to `addr`.
+#[must_use]
+pub fn build_response(instance: &str, host: &str, addr: Ipv4Addr, port: u16, ttl: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(128);
+    out.extend_from_slice(&0u16.to_be_bytes()); // id
+    out.extend_from_slice(&0x8400u16.to_be_bytes()); // flags: response, authoritative
+    out.extend_from_slice(&0u16.to_be_bytes()); // qdcount
+    out.extend_from_slice(&3u16.to_be_bytes()); // ancount: PTR, SRV, A
+    out.extend_from_slice(&0u16.to_be_bytes()); // nscount
+    out.extend_from_slice(&0u16.to_be_bytes()); // arcount
+
+    // PTR SERVICE_TYPE -> instance
+    encode_name(SERVICE_TYPE, &mut out);
+    out.extend_from_slice(&TYPE_PTR.to_be_bytes());
+    out.extend_from_slice(&CLASS_IN.to_be_bytes());
+    out.extend_from_slice(&ttl.to_be_bytes());
+    let ptr_rdata_len_pos = out.len();
+    out.extend_from_slice(&0u16.to_be_bytes());
+    let rdata_start = out.len();
+    encode_name(instance, &mut out);
+    let rdata_len = (out.len() - rdata_start) as u16;
+    out[ptr_rdata_len_pos..ptr_rdata_len_pos + 2].copy_from_slice(&rdata_len.to_be_bytes());
+
+    // SRV instance -> host:port
+    encode_name(instance, &mut out);
+    out.extend_from_slice(&TYPE_SRV.to_be_bytes());
+    out.extend_from_slice(&(CLASS_IN | CLASS_CACHE_FLUSH).to_be_bytes());
+    out.extend_from_slice(&ttl.to_be_bytes());
+    let srv_rdata_len_pos = out.len();
+    out.extend_from_slice(&0u16.to_be_bytes());
+    let rdata_start = out.len();
+    out.extend_from_slice(&0u16.to_be_bytes()); // priority
+    out.extend_from_slice(&0u16.to_be_bytes()); // weight
+    out.extend_from_slice(&port.to_be_bytes());
+    encode_name(host, &mut out);
+    let rdata_len = (out.len() - rdata_start) as u16;
+    out[srv_rdata_len_pos..srv_rdata_len_pos + 2].copy_from_slice(&rdata_len.to_be_bytes());
+
+    // A host -> addr
+    encode_name(host, &mut out);
+    out.extend_from_slice(&TYPE_A.to_be_bytes());
+    out.extend_from_slice(&(CLASS_IN | CLASS_CACHE_FLUSH).to_be_bytes());
+    out.extend_from_slice(&ttl.to_be_bytes());
+    out.extend_from_slice(&4u16.to_be_bytes());
+    out.extend_from_slice(&addr.octets());
+
+    out
+}
+
+/// Parses the query names out of a DNS message's question section, e.g.
+/// to check whether an incoming query is asking about [`SERVICE_TYPE`].
+pub fn parse_questions(buf: &[u8]) -> Result<Vec<String>, Error> {
+    if buf.len() < 12 {
+        return Err(Error::Truncated);
+    }
+
+    let qdcount = read_u16(buf, 4)?;
+    let mut pos = 12;
+    let mut names = Vec::new();
+
+    for _ in 0..qdcount {
+        let (name, next) = decode_name(buf, pos)?;
+        names.push(name);
+        pos = next + 4; // qtype + qclass
+    }
+
+    Ok(names)
+}
+
+/// Parses every PTR/SRV/A record out of the answer, authority, and
+/// additional sections of a DNS message (any other record type or section
+/// content is skipped). Does not touch the header's query/response flag,
+/// so it's equally happy parsing a response we received or (for tests) one
+/// we just built ourselves.
+pub fn parse_answers(buf: &[u8]) -> Result<Vec<Answer>, Error> {
+    if buf.len() < 12 {
+        return Err(Error::Truncated);
+    }
+
+    let qdcount = read_u16(buf, 4)?;
+    let ancount = read_u16(buf, 6)?;
+    let nscount = read_u16(buf, 8)?;
+    let arcount = read_u16(buf, 10)?;
+
+    let mut pos = 12;
+
+    for _ in 0..qdcount {
+        let (_, next) = decode_name(buf, pos)?;
+        pos = next + 4; // qtype + qclass
+    }
+
+    let mut answers = Vec::new();
+
+    for _ in 0..(ancount as u32 + nscount as u32 + arcount as u32) {
+        let (name, next) = decode_name(buf, pos)?;
+        pos = next;
+
+        let rtype = read_u16(buf, pos)?;
+        let _rclass = read_u16(buf, pos + 2)?;
+        let ttl = read_u32(buf, pos + 4)?;
+        let rdlength = read_u16(buf, pos + 8)? as usize;
+        let rdata_start = pos + 10;
+        pos = rdata_start + rdlength;
+
+        match rtype {
+            TYPE_PTR => {
+                let (target, _) = decode_name(buf, rdata_start)?;
+                answers.push(Answer::Ptr { ttl, target });
+            }
+            TYPE_SRV => {
+                let port = read_u16(buf, rdata_start + 4)?;
+                let (target, _) = decode_name(buf, rdata_start + 6)?;
+                answers.push(Answer::Srv { name, ttl, port, target });
+            }
+            TYPE_A => {
+                let octets = buf
+                    .get(rdata_start..rdata_start + 4)
+                    .ok_or(Error::Truncated)?;
+                answers.push(Answer::A {
+                    name,
+                    ttl,
+                    addr: Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(answers)
+}
@@ -0,0 +1,54 @@
+use super::*;
+
+pub const SCHEDULE_NOTIFY_PATH: &str = "led/notify/schedule";
+
+/// When a [`ScheduleNotify`] should fire. `At` needs the server's clock
+/// synced (see `wb_notifier_server::sntp`) since it's an absolute Unix
+/// time; `Every` just counts down from whenever the request is accepted,
+/// so it works even without a synced clock.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Schema)]
+pub enum Schedule {
+    /// Fire once, at this many seconds since the Unix epoch.
+    At { epoch_secs: u64 },
+    /// Fire every `secs` seconds, starting `secs` from now.
+    Every { secs: u32 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct ScheduleNotify {
+    pub notify: Notify,
+    pub schedule: Schedule,
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+pub struct ScheduleNotifyResponse(pub Result<(), ScheduleError>);
+
+/// What went wrong accepting a [`ScheduleNotify`], kept separate from
+/// [`DeviceError`] since nothing has touched the device yet at this
+/// point; the notify itself isn't applied until it fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Schema)]
+pub enum ScheduleError {
+    /// `Schedule::At` was requested, but the server's clock isn't synced
+    /// yet (no `--ntp-server` configured, or no successful query since
+    /// startup).
+    ClockNotSynced,
+    /// The server couldn't queue the schedule: its schedule queue is
+    /// currently full, or there is no schedule worker to queue it for.
+    Full,
+}
+
+impl std::fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScheduleError::ClockNotSynced => {
+                write!(
+                    f,
+                    "server clock is not synced, cannot schedule an absolute time"
+                )
+            }
+            ScheduleError::Full => write!(f, "server could not queue this schedule"),
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
@@ -0,0 +1,105 @@
+//! Runtime device configuration, modeled on ARTIQ's key/value config
+//! store: entries can be read back, written, or removed, with every
+//! mutation persisted to disk immediately so a restart picks up wherever
+//! the daemon left off instead of falling back to whatever `Server::new`
+//! was given.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::{error, fmt};
+
+use wb_notifier_proto::Device;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(_) => write!(f, "could not read/write the device config file"),
+            Error::Parse(_) => write!(f, "device config file is not valid JSON"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Parse(e) => Some(e),
+        }
+    }
+}
+
+/// The persisted device list backing `ListDevicesEndpoint`/
+/// `AddDeviceEndpoint`/`RemoveDeviceEndpoint`. Every `write`/`remove`
+/// rewrites `path` in full; this is a small, infrequently-changed list, so
+/// there's no need for an append-only log or partial update.
+pub struct DeviceConfig {
+    path: PathBuf,
+    devices: Vec<Device>,
+}
+
+impl DeviceConfig {
+    /// Loads the device list `path` already has on disk, seeded with
+    /// `initial` (normally whatever `Server::new` was given) if the file
+    /// doesn't exist yet.
+    pub fn load(path: PathBuf, initial: Vec<Device>) -> Result<Self, Error> {
+        let devices = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(Error::Parse)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => initial,
+            Err(e) => return Err(Error::Io(e)),
+        };
+
+        Ok(Self { path, devices })
+    }
+
+    /// The devices currently configured.
+    #[must_use]
+    pub fn read(&self) -> &[Device] {
+        &self.devices
+    }
+
+    /// Adds `device` and persists the updated list, unless a device by
+    /// that name is already configured.
+    pub fn write(&mut self, device: Device) -> Result<(), Error> {
+        if self.devices.iter().any(|d| d.name == device.name) {
+            return Ok(());
+        }
+
+        self.devices.push(device);
+        self.persist()
+    }
+
+    /// Removes the device named `name` and persists the updated list.
+    /// Returns the removed entry, or `None` if no such device was
+    /// configured.
+    pub fn remove(&mut self, name: &str) -> Result<Option<Device>, Error> {
+        let Some(idx) = self.devices.iter().position(|d| d.name == name) else {
+            return Ok(None);
+        };
+
+        let removed = self.devices.remove(idx);
+        self.persist()?;
+
+        Ok(Some(removed))
+    }
+
+    /// Whether a device by this name is already configured, without
+    /// mutating anything; lets a handler report
+    /// [`wb_notifier_proto::ConfigError::Duplicate`] before it goes to the
+    /// trouble of bringing the device up.
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.devices.iter().any(|d| d.name == name)
+    }
+
+    fn persist(&self) -> Result<(), Error> {
+        let json = serde_json::to_vec_pretty(&self.devices).map_err(Error::Parse)?;
+        fs::write(&self.path, json).map_err(Error::Io)
+    }
+}
@@ -6,23 +6,49 @@ use async_net::{SocketAddr, UdpSocket};
 use embedded_hal::blocking::delay::{DelayMs, DelayUs};
 use embedded_hal::blocking::i2c::{Write, WriteRead};
 use linux_embedded_hal::{Delay, I2cdev};
+use postcard::experimental::schema::Schema;
+use postcard_rpc::headered::extract_header_from_bytes;
 use postcard_rpc::{self, endpoint, Dispatch, Key, WireHeader};
-use serde::Deserialize;
-use wb_notifier_driver::bargraph::{Bargraph, Dimming};
+use serde::{Deserialize, Serialize};
+use wb_notifier_driver::bargraph::{Bargraph, Dimming, Display};
 use wb_notifier_driver::lcd::Lcd;
-use wb_notifier_driver::Sensors;
+use wb_notifier_driver::{Sensors, Slot};
 
+use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::error;
 use std::fmt;
 use std::future::Future;
 use std::io;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::Arc;
 
 use wb_notifier_driver;
 use wb_notifier_proto::*;
 
+mod config;
+mod signal;
+pub mod sntp;
 mod tasks;
+mod transport;
+
+#[cfg(feature = "mdns")]
+pub mod mdns;
+
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+
+#[cfg(feature = "scpi")]
+pub mod scpi;
+
+#[cfg(feature = "serial")]
+pub mod serial;
+
+use transport::{Transport, UdpTransport};
+
+#[cfg(feature = "serial")]
+use serial::{SerialConfig, SerialTransport};
 
 endpoint!(EchoEndpoint, Echo, EchoResponse, "debug/echo");
 endpoint!(SetLedEndpoint, SetLed, SetLedResponse, "led/set");
@@ -34,6 +60,13 @@ endpoint!(
 );
 endpoint!(NotifyEndpoint, Notify, NotifyResponse, "led/notify");
 endpoint!(AckEndpoint, Ack, AckResponse, "led/ack");
+endpoint!(
+    NotifyMirrorEndpoint,
+    Notify,
+    NotifyResponse,
+    "led/notify/mirror"
+);
+endpoint!(AckMirrorEndpoint, Ack, AckResponse, "led/ack/mirror");
 endpoint!(
     SetBacklightEndpoint,
     SetBacklight,
@@ -41,44 +74,318 @@ endpoint!(
     "lcd/backlight"
 );
 endpoint!(SendMsgEndpoint, SendMsg, SendMsgResponse, "lcd/msg");
+endpoint!(SelfTestEndpoint, SelfTest, SelfTestResponse, "led/selftest");
+endpoint!(
+    SetBlinkThresholdsEndpoint,
+    SetBlinkThresholds,
+    SetBlinkThresholdsResponse,
+    "led/blink_thresholds"
+);
+endpoint!(
+    ListDevicesEndpoint,
+    ListDevices,
+    ListDevicesResponse,
+    "config/device/list"
+);
+endpoint!(
+    AddDeviceEndpoint,
+    AddDevice,
+    AddDeviceResponse,
+    "config/device/add"
+);
+endpoint!(
+    RemoveDeviceEndpoint,
+    RemoveDevice,
+    RemoveDeviceResponse,
+    "config/device/remove"
+);
+endpoint!(
+    ErrorQueryEndpoint,
+    ErrorQuery,
+    LastErrorResponse,
+    "debug/error"
+);
+endpoint!(
+    ScheduleNotifyEndpoint,
+    ScheduleNotify,
+    ScheduleNotifyResponse,
+    "led/notify/schedule"
+);
+
+/// A channel handle that can come and go at runtime, the `blink`/`marquee`
+/// counterpart to [`wb_notifier_driver::Slot`]: every live `Context` shares
+/// the same slot, so a device brought up after startup by
+/// `AddDeviceEndpoint` is visible to UDP/serial/MQTT alike the moment its
+/// background task is spawned.
+type ChanSlot<T> = Rc<RefCell<Option<Sender<T>>>>;
+
+/// How many `(seq_no, key)` -> [`DispatchError`] entries [`ErrorLog`] keeps
+/// before evicting the oldest; a client is expected to poll
+/// `ErrorQueryEndpoint` soon after a request fails, not dig through
+/// arbitrary history.
+const ERROR_LOG_CAPACITY: usize = 32;
+
+/// Record of the most recent failures, shared by every front end
+/// (UDP/serial/SCPI/MQTT) rather than one per `Context`, so a failure on
+/// one transport is still visible to `ErrorQueryEndpoint` queried over
+/// another. Keyed on `(seq_no, key)` rather than just `seq_no` since
+/// `seq_no` alone can't disambiguate requests on different endpoints once
+/// it wraps.
+type ErrorLog = Rc<RefCell<VecDeque<((u32, Key), DispatchError)>>>;
+
+/// Records `err` under `(seq_no, key)`, evicting the oldest entry first if
+/// `log` is already at [`ERROR_LOG_CAPACITY`].
+fn record_error(log: &ErrorLog, seq_no: u32, key: Key, err: DispatchError) {
+    let mut log = log.borrow_mut();
+    if log.len() >= ERROR_LOG_CAPACITY {
+        log.pop_front();
+    }
+    log.push_back(((seq_no, key), err));
+}
+
+/// Maps a dispatch-level failure (one that never reached a handler at all)
+/// onto the wire-safe [`DispatchError`] a client gets back from
+/// `ErrorQueryEndpoint`.
+fn classify_error(err: &Error) -> DispatchError {
+    match err {
+        Error::NoMatch { .. } => DispatchError::NonexistentEndpoint,
+        Error::Parse(_) => DispatchError::Malformed,
+        Error::NoTransport | Error::Io(_) | Error::Init(_) | Error::Config(_) => {
+            DispatchError::Other
+        }
+        #[cfg(feature = "serial")]
+        Error::Serial(_) => DispatchError::Other,
+    }
+}
 
 pub struct Server {
     addr: SocketAddr,
     devices: Vec<Device>,
+    /// Don't exit when a driver call fails to talk to the I2C device;
+    /// mark it offline and keep retrying in the background instead. See
+    /// [`tasks::background::reconnect`].
+    relaxed: bool,
+    /// Other `wb-notifier` daemons to mirror `notify`/`ack` to. See
+    /// [`PeerFanout`].
+    peers: Vec<SocketAddr>,
+    /// Whether a `notify`/`ack` mirrored in from a peer should itself be
+    /// mirrored on to `peers` (full mesh) rather than only applied
+    /// locally (star topology, avoiding rebroadcast storms).
+    rebroadcast: bool,
+    /// Where to persist the live device list so `config/device/add` and
+    /// `config/device/remove` survive a restart. Without this, `devices`
+    /// is just the fixed startup list and the config endpoints don't
+    /// change it: `config/device/list` returns an empty list and add/remove
+    /// fail with `ConfigError::NotFound`. See [`config::DeviceConfig`].
+    device_config: Option<PathBuf>,
+    /// NTP server to sync the server's clock against, so `ScheduleNotify`
+    /// can accept `Schedule::At` (an absolute Unix time) instead of just
+    /// `Schedule::Every`. See [`sntp::sntp_task`].
+    sntp: Option<sntp::SntpConfig>,
+    #[cfg(feature = "mdns")]
+    mdns: Option<mdns::MdnsConfig>,
+    #[cfg(feature = "mqtt")]
+    mqtt: Option<mqtt::MqttConfig>,
+    #[cfg(feature = "scpi")]
+    scpi: Option<SocketAddr>,
+    #[cfg(feature = "serial")]
+    serial: Option<SerialConfig>,
 }
 
-struct Context<'ex, 'b, I2C, D>
-where
-    I2C: Write + WriteRead,
-{
+struct Context<'ex, 'b, I2C, D, X> {
     ex: &'b Rc<LocalExecutor<'ex>>,
-    sock: UdpSocket,
-    addr: Option<SocketAddr>,
-    blink_send: Option<Sender<tasks::background::BlinkInfo>>,
-    sensors: Sensors<'b, I2C, D>,
+    /// The transport bound to whichever request is currently being
+    /// dispatched; set fresh before every `dispatch.dispatch(...)` call so
+    /// handlers can reply over it without knowing if it's UDP or serial.
+    transport: Option<X>,
+    blink_send: ChanSlot<tasks::background::BlinkInfo>,
+    fault_send: Option<Sender<tasks::background::Fault>>,
+    marquee_send: ChanSlot<tasks::background::MarqueeInfo>,
+    peer_fanout: Option<PeerFanout>,
+    /// Set when the `mqtt` feature's broker bridge is configured,
+    /// regardless of which front end this particular `Context` belongs
+    /// to; see [`StatusPublish`].
+    status_publish: Option<StatusPublish>,
+    sensors: Sensors<I2C, D>,
+    /// Lets `AddDeviceEndpoint`'s handler mint a fresh I2C handle for a
+    /// device that didn't exist at startup, without ever having to name
+    /// the opaque type `shared_bus::new_std!` produces.
+    bus: Option<Rc<dyn Fn() -> I2C>>,
+    device_config: Option<Rc<RefCell<config::DeviceConfig>>>,
+    /// Backs `ErrorQueryEndpoint`. See [`ErrorLog`].
+    errors: ErrorLog,
+    /// Feeds `ScheduleNotifyEndpoint` requests to the process-wide
+    /// [`tasks::background::schedule`] worker; unlike `blink_send`/
+    /// `marquee_send` this is never cleared, since the worker isn't tied
+    /// to any one device.
+    schedule_send: ChanSlot<tasks::background::ScheduleInfo>,
+    /// Next id `schedule_notify_handler` mints for a
+    /// [`tasks::background::ScheduleInfo::Add`]; shared across every
+    /// `Context` so ids stay unique regardless of which transport a
+    /// `ScheduleNotify` arrives on.
+    schedule_next_id: Rc<std::cell::Cell<u64>>,
+    /// The server's best estimate of the real time, if `--ntp-server` is
+    /// configured and has synced at least once. See [`sntp::Clock`].
+    clock: sntp::Clock,
 }
 
-impl<'ex, 'b, I2C, D> Context<'ex, 'b, I2C, D>
+impl<'ex, 'b, I2C, D, X> Context<'ex, 'b, I2C, D, X>
 where
     I2C: Write + WriteRead,
 {
-    fn new(ex: &'b Rc<LocalExecutor<'ex>>, sock: UdpSocket) -> Self {
+    fn new(ex: &'b Rc<LocalExecutor<'ex>>) -> Self {
         Self {
             ex,
-            sock,
-            addr: None,
-            blink_send: None,
+            transport: None,
+            blink_send: Rc::new(RefCell::new(None)),
+            fault_send: None,
+            marquee_send: Rc::new(RefCell::new(None)),
+            peer_fanout: None,
+            status_publish: None,
             sensors: Sensors::new(),
+            bus: None,
+            device_config: None,
+            errors: Rc::new(RefCell::new(VecDeque::new())),
+            schedule_send: Rc::new(RefCell::new(None)),
+            schedule_next_id: Rc::new(std::cell::Cell::new(0)),
+            clock: sntp::Clock::new(),
         }
     }
 }
 
+/// Mirrors `notify`/`ack` to other `wb-notifier` daemons over UDP, so an
+/// alert raised on one bench lights up on every bench in `peers` and an
+/// `Ack` on any one of them clears it everywhere.
+///
+/// Mirrored frames are re-sent keyed to
+/// [`NotifyMirrorEndpoint`]/[`AckMirrorEndpoint`] instead of the
+/// client-facing endpoints, so `hdr.key` itself is the loop-prevention
+/// marker: [`notify_mirror_handler`]/[`ack_mirror_handler`] know the frame
+/// already came from a peer and, unless `rebroadcast` is set, don't mirror
+/// it out again.
+#[derive(Clone)]
+struct PeerFanout {
+    sock: UdpSocket,
+    peers: Vec<SocketAddr>,
+    rebroadcast: bool,
+    notify_key: Key,
+    ack_key: Key,
+}
+
+impl PeerFanout {
+    fn new(sock: UdpSocket, peers: Vec<SocketAddr>, rebroadcast: bool) -> Self {
+        Self {
+            sock,
+            peers,
+            rebroadcast,
+            notify_key: Key::for_path::<Notify>("led/notify/mirror"),
+            ack_key: Key::for_path::<Ack>("led/ack/mirror"),
+        }
+    }
+
+    /// Re-sends `payload` to every configured peer under `key`, except
+    /// `origin` (the peer it was mirrored in from, if any) so two
+    /// mutually-`rebroadcast`ing peers don't ping-pong the same frame back
+    /// and forth forever. Spawned onto `ex` rather than awaited, so a
+    /// dead/unreachable peer can't stall the handler replying to the
+    /// original request.
+    fn mirror<T>(
+        &self,
+        ex: &Rc<LocalExecutor<'_>>,
+        from_peer: bool,
+        origin: Option<SocketAddr>,
+        seq_no: u32,
+        key: Key,
+        payload: T,
+    ) where
+        T: Schema + Serialize + 'static,
+    {
+        if self.peers.is_empty() || (from_peer && !self.rebroadcast) {
+            return;
+        }
+
+        let sock = self.sock.clone();
+        let peers: Vec<SocketAddr> = self
+            .peers
+            .iter()
+            .copied()
+            .filter(|peer| Some(*peer) != origin)
+            .collect();
+
+        ex.spawn(async move {
+            let mut buf = vec![0u8; 1024];
+            let Ok(used) =
+                postcard_rpc::headered::to_slice_keyed(seq_no, key, &payload, &mut buf)
+            else {
+                return;
+            };
+
+            for peer in &peers {
+                let _ = sock.send_to(used, *peer).await;
+            }
+        })
+        .detach();
+    }
+}
+
+/// Topic a retained mirror of the last LED/backlight state a handler
+/// applied is published on, with a suffix identifying what changed
+/// appended (e.g. `workbench/status/3` for LED 3, `workbench/status/backlight`).
+pub(crate) const STATUS_TOPIC_PREFIX: &str = "workbench/status/";
+
+/// Publishes a retained status mirror to the MQTT broker from whichever
+/// handler just applied a change, the same way [`PeerFanout`] pushes a
+/// copy out to other daemons: a passive subscriber (e.g. an ESP32) can
+/// sync off `workbench/status/<led>` instead of polling, regardless of
+/// whether the change that triggered it came in over UDP, serial, SCPI,
+/// or MQTT itself. `None` when the `mqtt` feature's broker bridge isn't
+/// configured.
+#[derive(Clone)]
+pub(crate) struct StatusPublish {
+    outbound: Sender<(String, Vec<u8>, bool)>,
+}
+
+impl StatusPublish {
+    pub(crate) fn new(outbound: Sender<(String, Vec<u8>, bool)>) -> Self {
+        Self { outbound }
+    }
+
+    /// Publishes `payload`, retained, to `workbench/status/<suffix>`;
+    /// silently dropped if the MQTT publisher thread is gone.
+    pub(crate) async fn publish(&self, suffix: impl fmt::Display, payload: Vec<u8>) {
+        let _ = self
+            .outbound
+            .send((format!("{STATUS_TOPIC_PREFIX}{suffix}"), payload, true))
+            .await;
+    }
+}
+
+/// ASCII status payload for a [`StatusPublish`] mirror, matching the
+/// lenient plain-text encoding clients on the topic tree (not necessarily
+/// Rust) are expected to read.
+pub(crate) fn status_payload(status: Status) -> Vec<u8> {
+    match status {
+        Status::Ok => b"ok".to_vec(),
+        Status::Warning => b"yellow".to_vec(),
+        Status::Error => b"red".to_vec(),
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     Io(io::Error),
     Init(InitError),
     Parse(postcard::Error),
     NoMatch { key: Key, seq_no: u32 },
+    Config(config::Error),
+    /// A handler ran with no transport bound in its `Context` to reply
+    /// on. Every `dispatch.dispatch(...)` call site sets `Context::transport`
+    /// immediately beforehand, so this should never actually happen; it
+    /// exists so a future call site that forgets to set it fails a single
+    /// request instead of panicking the whole daemon.
+    NoTransport,
+    #[cfg(feature = "serial")]
+    Serial(serial::Error),
 }
 
 impl From<io::Error> for Error {
@@ -87,6 +394,19 @@ impl From<io::Error> for Error {
     }
 }
 
+impl From<config::Error> for Error {
+    fn from(value: config::Error) -> Self {
+        Self::Config(value)
+    }
+}
+
+#[cfg(feature = "serial")]
+impl From<serial::Error> for Error {
+    fn from(value: serial::Error) -> Self {
+        Self::Serial(value)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -96,6 +416,10 @@ impl fmt::Display for Error {
             Self::NoMatch { key, seq_no } => {
                 write!(f, "cannot dispatch sequence no {seq_no} with key {key:?}")
             }
+            Self::Config(_) => write!(f, "error loading persisted device config"),
+            Self::NoTransport => write!(f, "no transport bound to reply on"),
+            #[cfg(feature = "serial")]
+            Self::Serial(_) => write!(f, "error on serial transport"),
         }
     }
 }
@@ -107,26 +431,58 @@ impl error::Error for Error {
             Self::Init(i) => Some(i),
             Self::Parse(p) => Some(p),
             Self::NoMatch { key: _, seq_no: _ } => None,
+            Self::Config(c) => Some(c),
+            Self::NoTransport => None,
+            #[cfg(feature = "serial")]
+            Self::Serial(e) => Some(e),
         }
     }
 }
 
 #[derive(Debug)]
 pub enum InitError {
-    Driver(Driver),
+    Driver {
+        driver: Driver,
+        addr: u8,
+        reason: AbortReason,
+    },
     Dispatch(&'static str),
 }
 
+impl InitError {
+    /// The bus-fault classification behind [`Self::Driver`], or `Other`
+    /// for [`Self::Dispatch`] (which isn't a bus fault at all).
+    #[must_use]
+    pub fn reason(&self) -> AbortReason {
+        match self {
+            InitError::Driver { reason, .. } => *reason,
+            InitError::Dispatch(_) => AbortReason::Other,
+        }
+    }
+}
+
 impl fmt::Display for InitError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            InitError::Driver(d) => {
-                let drv = match d {
+            InitError::Driver {
+                driver,
+                addr,
+                reason,
+            } => {
+                let drv = match driver {
                     Driver::Bargraph => "bargraph",
                     Driver::Hd44780 => "lcd",
                 };
 
-                write!(f, "driver {drv} could not communicate with device")
+                match reason {
+                    AbortReason::NoAcknowledge => {
+                        write!(f, "{drv} device not present at addr {addr:#04x}")
+                    }
+                    _ => write!(
+                        f,
+                        "driver {drv} could not communicate with device at addr {addr:#04x}: {reason}"
+                    ),
+                }
             }
             InitError::Dispatch(_) => write!(f, "dispatch table failed to initialize"),
         }
@@ -136,7 +492,7 @@ impl fmt::Display for InitError {
 impl error::Error for InitError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
-            InitError::Driver(_) => None,
+            InitError::Driver { .. } => None,
             InitError::Dispatch(d) => {
                 let box_err = Box::<dyn error::Error + 'static>::from(*d);
                 Some(Box::<dyn error::Error + 'static>::leak(box_err))
@@ -148,112 +504,694 @@ impl error::Error for InitError {
 impl Server {
     #[must_use]
     pub fn new(addr: SocketAddr, devices: Vec<Device>) -> Self {
-        Self { addr, devices }
+        Self {
+            addr,
+            devices,
+            relaxed: false,
+            peers: Vec::new(),
+            rebroadcast: false,
+            device_config: None,
+            sntp: None,
+            #[cfg(feature = "mdns")]
+            mdns: None,
+            #[cfg(feature = "mqtt")]
+            mqtt: None,
+            #[cfg(feature = "scpi")]
+            scpi: None,
+            #[cfg(feature = "serial")]
+            serial: None,
+        }
     }
 
-    pub async fn main_loop(self, ex: Rc<LocalExecutor<'_>>) -> Result<(), Error> {
-        let socket = UdpSocket::bind(self.addr).await?;
-        let mut buf = vec![0u8; 1024];
+    /// Don't exit the moment a driver call fails to talk to the I2C
+    /// device: mark it offline, keep serving everything else, and retry
+    /// reopening/initializing the bus on a capped exponential backoff.
+    #[must_use]
+    pub fn with_relaxed(mut self, relaxed: bool) -> Self {
+        self.relaxed = relaxed;
+        self
+    }
+
+    /// Mirror `notify`/`ack` to every address in `peers` over UDP,
+    /// turning a set of independent daemons into a mesh. `rebroadcast`
+    /// controls whether a frame mirrored in from a peer is itself
+    /// mirrored on to `peers`, or only applied locally. See
+    /// [`PeerFanout`].
+    #[must_use]
+    pub fn with_peers(mut self, peers: Vec<SocketAddr>, rebroadcast: bool) -> Self {
+        self.peers = peers;
+        self.rebroadcast = rebroadcast;
+        self
+    }
+
+    /// Advertise this daemon over mDNS/DNS-SD as `instance` so `wbnc` can
+    /// discover it by name instead of a hardcoded address. See
+    /// [`mdns::advertiser_task`].
+    #[cfg(feature = "mdns")]
+    #[must_use]
+    pub fn with_mdns(mut self, instance: String, host: String) -> Self {
+        self.mdns = Some(mdns::MdnsConfig {
+            instance,
+            host,
+            addr: self.addr,
+        });
+        self
+    }
+
+    /// Bridge notifications to/from an MQTT broker in addition to the UDP
+    /// socket. See [`mqtt::mqtt_task`] for the topic tree this subscribes
+    /// to.
+    #[cfg(feature = "mqtt")]
+    #[must_use]
+    pub fn with_mqtt(mut self, cfg: mqtt::MqttConfig) -> Self {
+        self.mqtt = Some(cfg);
+        self
+    }
+
+    /// Accept the SCPI-style ASCII command protocol on `addr` in addition
+    /// to the postcard/UDP socket. See [`scpi::scpi_task`].
+    #[cfg(feature = "scpi")]
+    #[must_use]
+    pub fn with_scpi(mut self, addr: SocketAddr) -> Self {
+        self.scpi = Some(addr);
+        self
+    }
 
-        let mut bg = None;
-        let mut lcd = None;
-        let mut dispatch =
-            Dispatch::<Context<_, _>, Error, 16>::new(Context::new(&ex, socket.clone()));
+    /// Serve the same request/response schema over a COBS-framed serial
+    /// port instead of the UDP socket. See [`serial::SerialTransport`].
+    #[cfg(feature = "serial")]
+    #[must_use]
+    pub fn with_serial(mut self, cfg: SerialConfig) -> Self {
+        self.serial = Some(cfg);
+        self
+    }
+
+    /// Persist the live device list to `path` and load it back on the next
+    /// start, so devices added/removed at runtime via `config/device/add`
+    /// and `config/device/remove` survive a restart instead of reverting
+    /// to whatever `Server::new` was given. See [`config::DeviceConfig`].
+    #[must_use]
+    pub fn with_device_config(mut self, path: PathBuf) -> Self {
+        self.device_config = Some(path);
+        self
+    }
+
+    /// Sync the server's clock against `cfg.server` so `ScheduleNotify` can
+    /// accept `Schedule::At` (an absolute Unix time). Without this, only
+    /// `Schedule::Every` is usable. See [`sntp::sntp_task`].
+    #[must_use]
+    pub fn with_sntp(mut self, cfg: sntp::SntpConfig) -> Self {
+        self.sntp = Some(cfg);
+        self
+    }
+
+    pub async fn main_loop(self, ex: Rc<LocalExecutor<'_>>) -> Result<(), Error> {
+        let bg: Slot<Bargraph<_>> = Rc::new(RefCell::new(None));
+        let lcd: Slot<Lcd<_, _>> = Rc::new(RefCell::new(None));
+        let blink_send: ChanSlot<tasks::background::BlinkInfo> = Rc::new(RefCell::new(None));
+        let marquee_send: ChanSlot<tasks::background::MarqueeInfo> = Rc::new(RefCell::new(None));
+        // Shared across every front end (UDP/serial/SCPI/MQTT) rather than
+        // one per `Context`, so a failure recorded while handling a request
+        // on one transport is still visible to `ErrorQueryEndpoint` polled
+        // over a different one.
+        let errors: ErrorLog = Rc::new(RefCell::new(VecDeque::new()));
+        // Shared the same way as `errors`: one clock/handler-queue for the
+        // whole process, not one per front end, since neither is tied to
+        // any one device.
+        let clock = sntp::Clock::new();
+        let schedule_next_id = Rc::new(std::cell::Cell::new(0));
 
-        let i2c = I2cdev::new("/dev/i2c-1").map_err(|e| Error::Io(e.into()))?;
+        let i2c_path = "/dev/i2c-1";
+        let i2c = I2cdev::new(i2c_path).map_err(|e| Error::Io(e.into()))?;
         let bus: &'static _ = shared_bus::new_std!(I2cdev = i2c).unwrap();
+        let acquire: Rc<dyn Fn() -> _> = Rc::new(move || bus.acquire_i2c());
 
-        self.devices
-            .iter()
-            .map(|d| {
-                // Self::send_init_msg(&sensor_send, d)?;
+        let device_config = match &self.device_config {
+            Some(path) => Some(Rc::new(RefCell::new(config::DeviceConfig::load(
+                path.clone(),
+                self.devices.clone(),
+            )?))),
+            None => None,
+        };
 
-                match d.driver {
-                    Driver::Bargraph => {
-                        let arc_bg = Arc::new(Mutex::new(Bargraph::new(bus.acquire_i2c(), d.addr)));
-                        {
-                            let mut bg = arc_bg.try_lock_arc().unwrap();
+        let devices = match &device_config {
+            Some(cfg) => cfg.borrow().read().to_vec(),
+            None => self.devices.clone(),
+        };
 
-                            bg.initialize()
-                                .map_err(|_| Error::Init(InitError::Driver(Driver::Bargraph)))?;
+        for d in &devices {
+            init_device(&ex, &acquire, d, &bg, &lcd, &blink_send, &marquee_send)
+                .map_err(Error::Init)?;
+        }
 
-                            bg.set_dimming(Dimming::BRIGHTNESS_3_16)
-                                .map_err(|_| Error::Init(InitError::Driver(Driver::Bargraph)))?;
-                        }
+        #[cfg(feature = "mdns")]
+        if let Some(cfg) = self.mdns.clone() {
+            ex.spawn(mdns::advertiser_task(cfg)).detach();
+        }
 
-                        let (blink_send, blink_recv) = bounded(1);
-                        ex.spawn(tasks::background::blink(
-                            ex.clone(),
-                            arc_bg.clone(),
-                            blink_recv,
-                        ))
-                        .detach();
+        // `reconnect` runs whenever a device is configured, regardless of
+        // `--relaxed`: a SIGHUP-triggered reload (see `serve_udp`/
+        // `serve_serial`) needs it to re-init the bus on demand even when
+        // the daemon isn't set up to retry bus faults on its own.
+        let reconnect_send = if bg.borrow().is_some() || lcd.borrow().is_some() {
+            let (send, recv) = bounded(4);
+            ex.spawn(tasks::background::reconnect(
+                i2c_path.to_string(),
+                bg.borrow().clone(),
+                lcd.borrow().clone(),
+                recv,
+            ))
+            .detach();
+            Some(send)
+        } else {
+            None
+        };
 
-                        bg.replace(arc_bg);
-                        dispatch.context().blink_send = Some(blink_send);
-                    }
-                    Driver::Hd44780 => {
-                        let arc_lcd;
-                        {
-                            let delay = Delay {};
-                            let lcd = Lcd::new(bus.acquire_i2c(), delay, d.addr)
-                                .map_err(|_| Error::Init(InitError::Driver(Driver::Hd44780)))?;
-
-                            arc_lcd = Arc::new(Mutex::new(lcd));
-                            {
-                                let mut lcd = arc_lcd.try_lock_arc().unwrap();
-
-                                lcd.initialize()
-                                    .map_err(|_| Error::Init(InitError::Driver(Driver::Hd44780)))?;
-                            }
-                        }
+        // Handlers only auto-report a bus fault to `reconnect` in
+        // `--relaxed` mode; outside of it, a fault is a hard error and the
+        // only way back online is a restart or an explicit SIGHUP reload.
+        let fault_send = if self.relaxed {
+            reconnect_send.clone()
+        } else {
+            None
+        };
+
+        // The mirror socket has to share the daemon's own listening
+        // address/port, not some ephemeral one: a peer's `mirror`
+        // identifies (and excludes) the frame's origin by comparing the
+        // UDP source address it arrived from against its own `peers`
+        // list, and that list names every peer's main listening address.
+        // Sending from an ephemeral port would make every mirrored frame
+        // look like it came from nowhere any peer recognizes, defeating
+        // the rebroadcast-loop filter in `PeerFanout::mirror` entirely.
+        // In UDP mode this is just `socket` itself, shared rather than
+        // rebound; in serial mode (where nothing else binds `self.addr`)
+        // open it solely for mirroring, and only if there's a mesh to
+        // mirror to.
+        #[cfg(feature = "serial")]
+        let serial_configured = self.serial.is_some();
+        #[cfg(not(feature = "serial"))]
+        let serial_configured = false;
+
+        let udp_socket = if !serial_configured || !self.peers.is_empty() {
+            Some(UdpSocket::bind(self.addr).await?)
+        } else {
+            None
+        };
+
+        let peer_fanout = match (&udp_socket, self.peers.is_empty()) {
+            (_, true) => None,
+            (Some(sock), false) => Some(PeerFanout::new(
+                sock.clone(),
+                self.peers.clone(),
+                self.rebroadcast,
+            )),
+            (None, false) => unreachable!("udp_socket is bound whenever peers is non-empty"),
+        };
+
+        // Connects to the broker (if configured) before UDP/serial/SCPI are
+        // spawned, rather than letting `mqtt_task` dial in on its own,
+        // so `status_publish` wraps the one `outbound` sender every front
+        // end shares: a `notify`/`ack` applied over plain UDP still
+        // publishes the same retained `workbench/status/<led>` mirror a
+        // passive MQTT subscriber is watching, not just commands that
+        // actually arrived over the broker.
+        #[cfg(feature = "mqtt")]
+        let mqtt_channels = self.mqtt.clone().map(mqtt::spawn_client);
+        #[cfg(feature = "mqtt")]
+        let status_publish = mqtt_channels
+            .as_ref()
+            .map(|(_, outbound)| StatusPublish::new(outbound.clone()));
+        #[cfg(not(feature = "mqtt"))]
+        let status_publish: Option<StatusPublish> = None;
+
+        if let Some(cfg) = self.sntp.clone() {
+            ex.spawn(sntp::sntp_task(cfg, clock.clone())).detach();
+        }
+
+        // Single process-wide worker backing `ScheduleNotifyEndpoint`,
+        // same precedent as `reconnect`: every front end below gets a
+        // clone of `schedule_send` rather than spawning their own worker.
+        // Takes `bg` (the live slot, not a snapshot) so a device brought
+        // up or removed after a schedule was queued is still picked up
+        // correctly when it fires.
+        let (schedule_tx, schedule_rx) = bounded(4);
+        let schedule_send: ChanSlot<tasks::background::ScheduleInfo> =
+            Rc::new(RefCell::new(Some(schedule_tx)));
+        ex.spawn(tasks::background::schedule(
+            ex.clone(),
+            bg.clone(),
+            fault_send.clone(),
+            errors.clone(),
+            blink_send.clone(),
+            marquee_send.clone(),
+            peer_fanout.clone(),
+            status_publish.clone(),
+            schedule_rx,
+        ))
+        .detach();
+
+        // `scpi`/`reconnect` only ever see the devices configured at
+        // startup: both take a one-time snapshot of the slots rather than
+        // the slots themselves, so a device added later via
+        // `AddDeviceEndpoint` won't show up on the SCPI front end or get
+        // auto-reconnected on a bus fault until the daemon restarts.
+        // Routed through the same `Dispatch` table as UDP/MQTT/serial, so
+        // it needs `fault_send`/`peer_fanout`/`status_publish` already
+        // resolved above.
+        #[cfg(feature = "scpi")]
+        if let Some(addr) = self.scpi {
+            ex.spawn(scpi::scpi_task(
+                ex.clone(),
+                addr,
+                bg.borrow().clone(),
+                lcd.borrow().clone(),
+                blink_send.clone(),
+                marquee_send.clone(),
+                fault_send.clone(),
+                peer_fanout.clone(),
+                status_publish.clone(),
+                errors.clone(),
+                schedule_send.clone(),
+                schedule_next_id.clone(),
+                clock.clone(),
+            ))
+            .detach();
+        }
+
+        // Routed through the same `Dispatch` table as UDP/serial, so it
+        // needs `fault_send`/`peer_fanout` already resolved above.
+        #[cfg(feature = "mqtt")]
+        if let Some((inbound, outbound)) = mqtt_channels {
+            ex.spawn(mqtt::mqtt_task(
+                &ex,
+                inbound,
+                outbound,
+                bg.clone(),
+                lcd.clone(),
+                blink_send.clone(),
+                marquee_send.clone(),
+                fault_send.clone(),
+                peer_fanout.clone(),
+                status_publish.clone(),
+                acquire.clone(),
+                device_config.clone(),
+                errors.clone(),
+                schedule_send.clone(),
+                schedule_next_id.clone(),
+                clock.clone(),
+            ))
+            .detach();
+        }
+
+        #[cfg(feature = "serial")]
+        if let Some(cfg) = self.serial.clone() {
+            return serve_serial(
+                &ex,
+                cfg,
+                bg,
+                lcd,
+                blink_send,
+                marquee_send,
+                fault_send,
+                reconnect_send,
+                peer_fanout,
+                status_publish,
+                acquire,
+                device_config,
+                errors,
+                schedule_send,
+                schedule_next_id,
+                clock,
+            )
+            .await;
+        }
+
+        serve_udp(
+            &ex,
+            udp_socket.expect("udp_socket is always bound when serial isn't configured"),
+            bg,
+            lcd,
+            blink_send,
+            marquee_send,
+            fault_send,
+            reconnect_send,
+            peer_fanout,
+            status_publish,
+            acquire,
+            device_config,
+            errors,
+            schedule_send,
+            schedule_next_id,
+            clock,
+        )
+        .await
+    }
+}
+
+/// Brings up one configured device over a freshly acquired I2C handle and
+/// spawns its background task (`blink`/`marquee`), storing the result in
+/// the matching shared slot. Called once per device at startup, and again
+/// from `AddDeviceEndpoint`'s handler for a device configured at runtime;
+/// either way, every already-running `Dispatch` table sees the change as
+/// soon as this returns, since `bg_slot`/`lcd_slot` are the exact `Rc`s
+/// every `Context`'s `Sensors` points at.
+fn init_device<I2C, E, D>(
+    ex: &Rc<LocalExecutor<'_>>,
+    acquire: &Rc<dyn Fn() -> I2C>,
+    device: &Device,
+    bg_slot: &Slot<Bargraph<I2C>>,
+    lcd_slot: &Slot<Lcd<I2C, D>>,
+    blink_send: &ChanSlot<tasks::background::BlinkInfo>,
+    marquee_send: &ChanSlot<tasks::background::MarqueeInfo>,
+) -> Result<(), InitError>
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + std::error::Error + 'static,
+    D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+{
+    match device.driver {
+        Driver::Bargraph => {
+            let arc_bg = Arc::new(Mutex::new(Bargraph::new(acquire(), device.addr)));
+            {
+                let mut bg = arc_bg.try_lock_arc().unwrap();
+
+                bg.initialize().map_err(|e| InitError::Driver {
+                    driver: Driver::Bargraph,
+                    addr: device.addr,
+                    reason: e.abort_reason().unwrap_or(AbortReason::Other),
+                })?;
+
+                bg.set_dimming(Dimming::BRIGHTNESS_3_16)
+                    .map_err(|e| InitError::Driver {
+                        driver: Driver::Bargraph,
+                        addr: device.addr,
+                        reason: e.abort_reason().unwrap_or(AbortReason::Other),
+                    })?;
+            }
+
+            let (send, recv) = bounded(1);
+            ex.spawn(tasks::background::blink(ex.clone(), arc_bg.clone(), recv))
+                .detach();
+
+            bg_slot.borrow_mut().replace(arc_bg);
+            blink_send.borrow_mut().replace(send);
+        }
+        Driver::Hd44780 => {
+            let delay = Delay {};
+            // `Lcd::new`/`initialize` surface `hd44780_driver`'s own error
+            // type, which doesn't preserve the underlying I2C failure to
+            // classify, so these two always report `Other`.
+            let lcd = Lcd::new(acquire(), delay, device.addr)
+                .map_err(|_| InitError::Driver {
+                    driver: Driver::Hd44780,
+                    addr: device.addr,
+                    reason: AbortReason::Other,
+                })?;
+
+            let arc_lcd = Arc::new(Mutex::new(lcd));
+            {
+                let mut lcd = arc_lcd.try_lock_arc().unwrap();
+
+                lcd.initialize().map_err(|_| InitError::Driver {
+                    driver: Driver::Hd44780,
+                    addr: device.addr,
+                    reason: AbortReason::Other,
+                })?;
+            }
+
+            let (send, recv) = bounded(1);
+            ex.spawn(tasks::background::marquee(ex.clone(), arc_lcd.clone(), recv))
+                .detach();
+
+            lcd_slot.borrow_mut().replace(arc_lcd);
+            marquee_send.borrow_mut().replace(send);
+        }
+    }
+
+    Ok(())
+}
+
+fn register_handlers<I2C, E, D, X>(
+    dispatch: &mut Dispatch<Context<'_, '_, I2C, D, X>, Error, 16>,
+) -> Result<(), Error>
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + std::error::Error + 'static,
+    D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+    X: Transport + 'static,
+{
+    dispatch
+        .add_handler::<EchoEndpoint>(echo_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+    dispatch
+        .add_handler::<SetLedEndpoint>(set_led_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+    dispatch
+        .add_handler::<SetDimmingEndpoint>(set_dimming_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+    dispatch
+        .add_handler::<NotifyEndpoint>(notify_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+    dispatch
+        .add_handler::<AckEndpoint>(ack_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+    dispatch
+        .add_handler::<NotifyMirrorEndpoint>(notify_mirror_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+    dispatch
+        .add_handler::<AckMirrorEndpoint>(ack_mirror_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+    dispatch
+        .add_handler::<SetBacklightEndpoint>(set_backlight_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+    dispatch
+        .add_handler::<SendMsgEndpoint>(send_msg_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+    dispatch
+        .add_handler::<SelfTestEndpoint>(self_test_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+    dispatch
+        .add_handler::<SetBlinkThresholdsEndpoint>(set_blink_thresholds_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+    dispatch
+        .add_handler::<ListDevicesEndpoint>(list_devices_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+    dispatch
+        .add_handler::<AddDeviceEndpoint>(add_device_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+    dispatch
+        .add_handler::<RemoveDeviceEndpoint>(remove_device_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+    dispatch
+        .add_handler::<ErrorQueryEndpoint>(error_query_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+    dispatch
+        .add_handler::<ScheduleNotifyEndpoint>(schedule_notify_handler)
+        .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
+
+    Ok(())
+}
+
+enum FinishedFirst<U, T> {
+    Us(U),
+    Them(T),
+}
+
+/// Races `us` against `them`, returning whichever finishes first. Used to
+/// race a blocking receive loop against the shutdown signal.
+async fn select<FU, FT, U, T>(us: FU, them: FT) -> FinishedFirst<U, T>
+where
+    FU: Future<Output = U>,
+    FT: Future<Output = T>,
+{
+    let us = async {
+        let res = us.await;
+        FinishedFirst::Us(res)
+    };
+
+    let them = async {
+        let res = them.await;
+        FinishedFirst::Them(res)
+    };
+
+    us.or(them).await
+}
+
+/// Leaves the hardware in a known-off state before the daemon exits:
+/// blanks every LED and makes sure the display itself is left on (rather
+/// than mid-blink) so it reads as "off", not "stuck".
+async fn shutdown_cleanup<I2C, E>(bg: &Slot<Bargraph<I2C>>)
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + std::error::Error + 'static,
+{
+    if let Some(bg) = bg.borrow().clone() {
+        blocking::unblock(move || {
+            let mut bg = bg.lock_arc_blocking();
+            let _ = bg.clear_all();
+            let _ = bg.set_display(Display::ON);
+        })
+        .await;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn serve_udp<I2C, E, D>(
+    ex: &Rc<LocalExecutor<'_>>,
+    socket: UdpSocket,
+    bg: Slot<Bargraph<I2C>>,
+    lcd: Slot<Lcd<I2C, D>>,
+    blink_send: ChanSlot<tasks::background::BlinkInfo>,
+    marquee_send: ChanSlot<tasks::background::MarqueeInfo>,
+    fault_send: Option<Sender<tasks::background::Fault>>,
+    reconnect_send: Option<Sender<tasks::background::Fault>>,
+    peer_fanout: Option<PeerFanout>,
+    status_publish: Option<StatusPublish>,
+    acquire: Rc<dyn Fn() -> I2C>,
+    device_config: Option<Rc<RefCell<config::DeviceConfig>>>,
+    errors: ErrorLog,
+    schedule_send: ChanSlot<tasks::background::ScheduleInfo>,
+    schedule_next_id: Rc<std::cell::Cell<u64>>,
+    clock: sntp::Clock,
+) -> Result<(), Error>
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + std::error::Error + 'static,
+    D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+{
+    let mut buf = vec![0u8; 1024];
+
+    let mut dispatch = Dispatch::<Context<_, _, UdpTransport>, Error, 16>::new(Context::new(ex));
+    dispatch.context().sensors.bargraph = Some(bg.clone());
+    dispatch.context().sensors.lcd = Some(lcd.clone());
+    dispatch.context().blink_send = blink_send;
+    dispatch.context().marquee_send = marquee_send;
+    dispatch.context().fault_send = fault_send;
+    dispatch.context().peer_fanout = peer_fanout;
+    dispatch.context().status_publish = status_publish;
+    dispatch.context().bus = Some(acquire);
+    dispatch.context().device_config = device_config;
+    dispatch.context().errors = errors;
+    dispatch.context().schedule_send = schedule_send;
+    dispatch.context().schedule_next_id = schedule_next_id;
+    dispatch.context().clock = clock;
+
+    register_handlers(&mut dispatch)?;
 
-                        lcd.replace(arc_lcd);
+    loop {
+        match select(socket.recv_from(&mut buf), signal::wait_for_signal()).await {
+            FinishedFirst::Us(Ok((n, peer))) => {
+                dispatch.context().transport = Some(UdpTransport {
+                    sock: socket.clone(),
+                    peer,
+                });
+                match dispatch.dispatch(&buf[..n]) {
+                    Ok(()) => {}
+                    Err(e) => {
+                        if let Ok((hdr, _)) = extract_header_from_bytes(&buf[..n]) {
+                            record_error(
+                                &dispatch.context().errors,
+                                hdr.seq_no,
+                                hdr.key,
+                                classify_error(&e),
+                            );
+                        }
+                        println!("Need to handle error: {e:?}");
                     }
                 }
-
-                Ok(())
-            })
-            .collect::<Result<Vec<()>, Error>>()?;
-
-        dispatch.context().sensors.bargraph = bg.as_ref();
-        dispatch.context().sensors.lcd = lcd.as_ref();
-
-        dispatch
-            .add_handler::<EchoEndpoint>(echo_handler)
-            .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
-        dispatch
-            .add_handler::<SetLedEndpoint>(set_led_handler)
-            .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
-        dispatch
-            .add_handler::<SetDimmingEndpoint>(set_dimming_handler)
-            .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
-        dispatch
-            .add_handler::<NotifyEndpoint>(notify_handler)
-            .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
-        dispatch
-            .add_handler::<AckEndpoint>(ack_handler)
-            .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
-        dispatch
-            .add_handler::<SetBacklightEndpoint>(set_backlight_handler)
-            .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
-        dispatch
-            .add_handler::<SendMsgEndpoint>(send_msg_handler)
-            .map_err(|e| Error::Init(InitError::Dispatch(e)))?;
-
-        loop {
-            let (n, addr) = socket.recv_from(&mut buf).await?;
-            dispatch.context().addr = Some(addr);
-            match dispatch.dispatch(&buf[..n]) {
-                Ok(()) => {}
-                Err(e) => {
-                    println!("Need to handle error: {e:?}");
+            }
+            FinishedFirst::Us(Err(e)) => return Err(e.into()),
+            FinishedFirst::Them(Ok(signal::SignalEvent::Shutdown)) | FinishedFirst::Them(Err(_)) => {
+                shutdown_cleanup(&bg).await;
+                return Ok(());
+            }
+            FinishedFirst::Them(Ok(signal::SignalEvent::Reload)) => {
+                if let Some(reconnect_send) = &reconnect_send {
+                    let _ = reconnect_send.try_send(tasks::background::Fault::Bargraph);
                 }
             }
         }
+    }
+}
+
+#[cfg(feature = "serial")]
+#[allow(clippy::too_many_arguments)]
+async fn serve_serial<I2C, E, D>(
+    ex: &Rc<LocalExecutor<'_>>,
+    cfg: SerialConfig,
+    bg: Slot<Bargraph<I2C>>,
+    lcd: Slot<Lcd<I2C, D>>,
+    blink_send: ChanSlot<tasks::background::BlinkInfo>,
+    marquee_send: ChanSlot<tasks::background::MarqueeInfo>,
+    fault_send: Option<Sender<tasks::background::Fault>>,
+    reconnect_send: Option<Sender<tasks::background::Fault>>,
+    peer_fanout: Option<PeerFanout>,
+    status_publish: Option<StatusPublish>,
+    acquire: Rc<dyn Fn() -> I2C>,
+    device_config: Option<Rc<RefCell<config::DeviceConfig>>>,
+    errors: ErrorLog,
+    schedule_send: ChanSlot<tasks::background::ScheduleInfo>,
+    schedule_next_id: Rc<std::cell::Cell<u64>>,
+    clock: sntp::Clock,
+) -> Result<(), Error>
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + std::error::Error + 'static,
+    D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+{
+    let port = SerialTransport::open(&cfg)?;
+    let mut raw = Vec::with_capacity(1024);
+    let mut buf = vec![0u8; 1024];
+
+    let mut dispatch =
+        Dispatch::<Context<_, _, SerialTransport>, Error, 16>::new(Context::new(ex));
+    dispatch.context().sensors.bargraph = Some(bg.clone());
+    dispatch.context().sensors.lcd = Some(lcd.clone());
+    dispatch.context().blink_send = blink_send;
+    dispatch.context().marquee_send = marquee_send;
+    dispatch.context().fault_send = fault_send;
+    dispatch.context().peer_fanout = peer_fanout;
+    dispatch.context().status_publish = status_publish;
+    dispatch.context().bus = Some(acquire);
+    dispatch.context().device_config = device_config;
+    dispatch.context().errors = errors;
+    dispatch.context().schedule_send = schedule_send;
+    dispatch.context().schedule_next_id = schedule_next_id;
+    dispatch.context().clock = clock;
 
-        #[allow(unreachable_code)]
-        Ok(())
+    register_handlers(&mut dispatch)?;
+
+    loop {
+        match select(port.recv_frame(&mut raw, &mut buf), signal::wait_for_signal()).await {
+            FinishedFirst::Us(Ok(n)) => {
+                dispatch.context().transport = Some(port.clone());
+                match dispatch.dispatch(&buf[..n]) {
+                    Ok(()) => {}
+                    Err(e) => {
+                        if let Ok((hdr, _)) = extract_header_from_bytes(&buf[..n]) {
+                            record_error(
+                                &dispatch.context().errors,
+                                hdr.seq_no,
+                                hdr.key,
+                                classify_error(&e),
+                            );
+                        }
+                        println!("Need to handle error: {e:?}");
+                    }
+                }
+            }
+            FinishedFirst::Us(Err(e)) => return Err(e.into()),
+            FinishedFirst::Them(Ok(signal::SignalEvent::Shutdown)) | FinishedFirst::Them(Err(_)) => {
+                shutdown_cleanup(&bg).await;
+                return Ok(());
+            }
+            FinishedFirst::Them(Ok(signal::SignalEvent::Reload)) => {
+                if let Some(reconnect_send) = &reconnect_send {
+                    let _ = reconnect_send.try_send(tasks::background::Fault::Bargraph);
+                }
+            }
+        }
     }
 }
 
@@ -276,151 +1214,424 @@ where
     }
 }
 
-fn set_led_handler<I2C, E, D>(
+fn set_led_handler<I2C, E, D, X>(
     hdr: &WireHeader,
-    ctx: &mut Context<'_, '_, I2C, D>,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
     bytes: &[u8],
 ) -> Result<(), Error>
 where
     I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
-    E: Send + 'static,
+    E: Send + std::error::Error + 'static,
+    X: Transport + 'static,
 {
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
     deserialize_detach(ctx.ex, bytes, |msg| {
         tasks::handlers::set_led(
             ctx.ex.clone(),
             hdr.seq_no,
             hdr.key,
-            (ctx.sock.clone(), ctx.addr.unwrap()),
-            ctx.sensors.bargraph.unwrap().clone(),
+            transport,
+            ctx.fault_send.clone(),
+            ctx.errors.clone(),
+            ctx.sensors.bargraph(),
             msg,
         )
     })
 }
 
-fn set_dimming_handler<I2C, E, D>(
+fn set_dimming_handler<I2C, E, D, X>(
     hdr: &WireHeader,
-    ctx: &mut Context<'_, '_, I2C, D>,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
     bytes: &[u8],
 ) -> Result<(), Error>
 where
     I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
-    E: Send + 'static,
+    E: Send + std::error::Error + 'static,
+    X: Transport + 'static,
 {
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
     deserialize_detach(ctx.ex, bytes, |msg| {
         tasks::handlers::set_dimming(
             ctx.ex.clone(),
             hdr.seq_no,
             hdr.key,
-            (ctx.sock.clone(), ctx.addr.unwrap()),
-            ctx.sensors.bargraph.unwrap().clone(),
+            transport,
+            ctx.fault_send.clone(),
+            ctx.errors.clone(),
+            ctx.sensors.bargraph(),
             msg,
         )
     })
 }
 
-fn notify_handler<I2C, E, D>(
+fn self_test_handler<I2C, E, D, X>(
     hdr: &WireHeader,
-    ctx: &mut Context<'_, '_, I2C, D>,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
     bytes: &[u8],
 ) -> Result<(), Error>
 where
     I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
-    E: Send + 'static,
+    E: Send + std::error::Error + 'static,
+    X: Transport + 'static,
 {
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
+    deserialize_detach(ctx.ex, bytes, |msg| {
+        tasks::handlers::self_test(
+            ctx.ex.clone(),
+            hdr.seq_no,
+            hdr.key,
+            transport,
+            ctx.fault_send.clone(),
+            ctx.errors.clone(),
+            ctx.sensors.bargraph(),
+            msg,
+        )
+    })
+}
+
+fn set_blink_thresholds_handler<I2C, D, E, X>(
+    hdr: &WireHeader,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
+    bytes: &[u8],
+) -> Result<(), Error>
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + std::error::Error + 'static,
+    X: Transport + 'static,
+{
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
+    deserialize_detach(ctx.ex, bytes, |msg| {
+        tasks::handlers::set_blink_thresholds(
+            ctx.ex.clone(),
+            hdr.seq_no,
+            hdr.key,
+            transport,
+            ctx.blink_send.borrow().clone(),
+            msg,
+        )
+    })
+}
+
+fn notify_handler<I2C, E, D, X>(
+    hdr: &WireHeader,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
+    bytes: &[u8],
+) -> Result<(), Error>
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + std::error::Error + 'static,
+    X: Transport + 'static,
+{
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
     deserialize_detach(ctx.ex, bytes, |msg| {
         tasks::handlers::notify(
             ctx.ex.clone(),
             hdr.seq_no,
             hdr.key,
-            (ctx.sock.clone(), ctx.addr.unwrap()),
-            ctx.blink_send.clone().unwrap(),
-            ctx.sensors.bargraph.unwrap().clone(),
+            transport,
+            ctx.fault_send.clone(),
+            ctx.errors.clone(),
+            ctx.blink_send.borrow().clone(),
+            ctx.marquee_send.borrow().clone(),
+            ctx.peer_fanout.clone(),
+            ctx.status_publish.clone(),
+            false,
+            ctx.sensors.bargraph(),
             msg,
         )
     })
 }
 
-fn ack_handler<I2C, E, D>(
+/// Same as [`notify_handler`], but for frames mirrored in from a peer:
+/// dispatched on [`NotifyMirrorEndpoint`] instead of [`NotifyEndpoint`],
+/// so `hdr.key` already marks it as not-locally-originated.
+fn notify_mirror_handler<I2C, E, D, X>(
     hdr: &WireHeader,
-    ctx: &mut Context<'_, '_, I2C, D>,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
     bytes: &[u8],
 ) -> Result<(), Error>
 where
     I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
-    E: Send + 'static,
+    E: Send + std::error::Error + 'static,
+    X: Transport + 'static,
 {
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
+    deserialize_detach(ctx.ex, bytes, |msg| {
+        tasks::handlers::notify(
+            ctx.ex.clone(),
+            hdr.seq_no,
+            hdr.key,
+            transport,
+            ctx.fault_send.clone(),
+            ctx.errors.clone(),
+            ctx.blink_send.borrow().clone(),
+            ctx.marquee_send.borrow().clone(),
+            ctx.peer_fanout.clone(),
+            ctx.status_publish.clone(),
+            true,
+            ctx.sensors.bargraph(),
+            msg,
+        )
+    })
+}
+
+fn ack_handler<I2C, E, D, X>(
+    hdr: &WireHeader,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
+    bytes: &[u8],
+) -> Result<(), Error>
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + std::error::Error + 'static,
+    X: Transport + 'static,
+{
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
     deserialize_detach(ctx.ex, bytes, |msg| {
         tasks::handlers::ack(
             ctx.ex.clone(),
             hdr.seq_no,
             hdr.key,
-            (ctx.sock.clone(), ctx.addr.unwrap()),
-            ctx.blink_send.clone().unwrap(),
-            ctx.sensors.bargraph.unwrap().clone(),
+            transport,
+            ctx.fault_send.clone(),
+            ctx.errors.clone(),
+            ctx.blink_send.borrow().clone(),
+            ctx.marquee_send.borrow().clone(),
+            ctx.peer_fanout.clone(),
+            ctx.status_publish.clone(),
+            false,
+            ctx.sensors.bargraph(),
             msg,
         )
     })
 }
 
-fn echo_handler<I2C, D, E>(
+/// Same as [`ack_handler`], but for frames mirrored in from a peer:
+/// dispatched on [`AckMirrorEndpoint`] instead of [`AckEndpoint`], so
+/// `hdr.key` already marks it as not-locally-originated.
+fn ack_mirror_handler<I2C, E, D, X>(
     hdr: &WireHeader,
-    ctx: &mut Context<'_, '_, I2C, D>,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
     bytes: &[u8],
 ) -> Result<(), Error>
 where
     I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
-    E: Send + 'static,
+    E: Send + std::error::Error + 'static,
+    X: Transport + 'static,
 {
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
     deserialize_detach(ctx.ex, bytes, |msg| {
-        tasks::handlers::echo(
+        tasks::handlers::ack(
             ctx.ex.clone(),
             hdr.seq_no,
             hdr.key,
-            (ctx.sock.clone(), ctx.addr.unwrap()),
+            transport,
+            ctx.fault_send.clone(),
+            ctx.errors.clone(),
+            ctx.blink_send.borrow().clone(),
+            ctx.marquee_send.borrow().clone(),
+            ctx.peer_fanout.clone(),
+            ctx.status_publish.clone(),
+            true,
+            ctx.sensors.bargraph(),
             msg,
         )
     })
 }
 
-fn set_backlight_handler<I2C, E, D>(
+fn echo_handler<I2C, D, E, X>(
+    hdr: &WireHeader,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
+    bytes: &[u8],
+) -> Result<(), Error>
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + std::error::Error + 'static,
+    X: Transport + 'static,
+{
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
+    deserialize_detach(ctx.ex, bytes, |msg| {
+        tasks::handlers::echo(ctx.ex.clone(), hdr.seq_no, hdr.key, transport, msg)
+    })
+}
+
+fn set_backlight_handler<I2C, E, D, X>(
     hdr: &WireHeader,
-    ctx: &mut Context<'_, '_, I2C, D>,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
     bytes: &[u8],
 ) -> Result<(), Error>
 where
     I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
-    E: Send + 'static,
+    E: Send + std::error::Error + 'static,
     D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+    X: Transport + 'static,
 {
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
     deserialize_detach(ctx.ex, bytes, |msg| {
         tasks::handlers::set_backlight(
             ctx.ex.clone(),
             hdr.seq_no,
             hdr.key,
-            (ctx.sock.clone(), ctx.addr.unwrap()),
-            ctx.sensors.lcd.unwrap().clone(),
+            transport,
+            ctx.fault_send.clone(),
+            ctx.errors.clone(),
+            ctx.status_publish.clone(),
+            ctx.sensors.lcd(),
             msg,
         )
     })
 }
 
-fn send_msg_handler<I2C, E, D>(
+fn send_msg_handler<I2C, E, D, X>(
     hdr: &WireHeader,
-    ctx: &mut Context<'_, '_, I2C, D>,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
     bytes: &[u8],
 ) -> Result<(), Error>
 where
     I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
-    E: Send + 'static,
+    E: Send + std::error::Error + 'static,
     D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+    X: Transport + 'static,
 {
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
     deserialize_detach(ctx.ex, bytes, |msg| {
         tasks::handlers::send_msg(
             ctx.ex.clone(),
             hdr.seq_no,
             hdr.key,
-            (ctx.sock.clone(), ctx.addr.unwrap()),
-            ctx.sensors.lcd.unwrap().clone(),
+            transport,
+            ctx.sensors.lcd(),
+            msg,
+        )
+    })
+}
+
+fn list_devices_handler<I2C, D, E, X>(
+    hdr: &WireHeader,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
+    bytes: &[u8],
+) -> Result<(), Error>
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + std::error::Error + 'static,
+    X: Transport + 'static,
+{
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
+    deserialize_detach(ctx.ex, bytes, |msg| {
+        tasks::handlers::list_devices(
+            ctx.ex.clone(),
+            hdr.seq_no,
+            hdr.key,
+            transport,
+            ctx.device_config.clone(),
+            msg,
+        )
+    })
+}
+
+fn add_device_handler<I2C, E, D, X>(
+    hdr: &WireHeader,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
+    bytes: &[u8],
+) -> Result<(), Error>
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + std::error::Error + 'static,
+    D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+    X: Transport + 'static,
+{
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
+    deserialize_detach(ctx.ex, bytes, |msg| {
+        tasks::handlers::add_device(
+            ctx.ex.clone(),
+            hdr.seq_no,
+            hdr.key,
+            transport,
+            ctx.device_config.clone(),
+            ctx.errors.clone(),
+            ctx.bus.clone(),
+            ctx.sensors.bargraph.clone(),
+            ctx.sensors.lcd.clone(),
+            ctx.blink_send.clone(),
+            ctx.marquee_send.clone(),
+            msg,
+        )
+    })
+}
+
+fn remove_device_handler<I2C, D, X>(
+    hdr: &WireHeader,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
+    bytes: &[u8],
+) -> Result<(), Error>
+where
+    I2C: Write + WriteRead,
+    X: Transport + 'static,
+{
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
+    deserialize_detach(ctx.ex, bytes, |msg| {
+        tasks::handlers::remove_device(
+            ctx.ex.clone(),
+            hdr.seq_no,
+            hdr.key,
+            transport,
+            ctx.device_config.clone(),
+            ctx.errors.clone(),
+            ctx.sensors.bargraph.clone(),
+            ctx.sensors.lcd.clone(),
+            ctx.blink_send.clone(),
+            ctx.marquee_send.clone(),
+            msg,
+        )
+    })
+}
+
+fn error_query_handler<I2C, D, X>(
+    hdr: &WireHeader,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
+    bytes: &[u8],
+) -> Result<(), Error>
+where
+    I2C: Write + WriteRead,
+    X: Transport + 'static,
+{
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
+    deserialize_detach(ctx.ex, bytes, |msg| {
+        tasks::handlers::error_query(
+            ctx.ex.clone(),
+            hdr.seq_no,
+            hdr.key,
+            transport,
+            ctx.errors.clone(),
+            msg,
+        )
+    })
+}
+
+fn schedule_notify_handler<I2C, D, X>(
+    hdr: &WireHeader,
+    ctx: &mut Context<'_, '_, I2C, D, X>,
+    bytes: &[u8],
+) -> Result<(), Error>
+where
+    I2C: Write + WriteRead,
+    X: Transport + 'static,
+{
+    let transport = ctx.transport.clone().ok_or(Error::NoTransport)?;
+    let id = ctx.schedule_next_id.get();
+    ctx.schedule_next_id.set(id + 1);
+    let clock = ctx.clock.clone();
+    let schedule_send = ctx.schedule_send.borrow().clone();
+    let errors = ctx.errors.clone();
+    deserialize_detach(ctx.ex, bytes, |msg| {
+        tasks::handlers::schedule_notify(
+            ctx.ex.clone(),
+            hdr.seq_no,
+            hdr.key,
+            transport,
+            errors,
+            clock,
+            schedule_send,
+            id,
             msg,
         )
     })
@@ -0,0 +1,65 @@
+//! Announces this daemon on the LAN so `wbnc` can discover it by name
+//! instead of a hardcoded address, via a minimal mDNS/DNS-SD responder.
+//! See [`wb_notifier_proto::mdns`] for the record encode/decode this task
+//! drives.
+use std::io;
+use std::net::{IpAddr, Ipv4Addr};
+
+use async_net::{SocketAddr, UdpSocket};
+use wb_notifier_proto::mdns::{self, MDNS_PORT, MULTICAST_ADDR_V4};
+
+/// TTL mDNS advertises records with; RFC 6762 recommends a short TTL for
+/// hosts that may move/disappear without sending a goodbye packet.
+const RECORD_TTL: u32 = 120;
+
+#[derive(Debug, Clone)]
+pub struct MdnsConfig {
+    /// This instance's DNS-SD service name, e.g. `"bench1"` becomes
+    /// `"bench1._wbnotifier._udp.local."`.
+    pub instance: String,
+    /// Host name to advertise, e.g. `"bench1.local."`.
+    pub host: String,
+    /// The address clients should connect to, i.e. [`crate::Server`]'s own
+    /// UDP listen address.
+    pub addr: SocketAddr,
+}
+
+/// Binds the mDNS multicast group and answers every [`mdns::SERVICE_TYPE`]
+/// query it sees with a PTR/SRV/A response advertising `cfg`. Runs until
+/// the socket errors.
+pub async fn advertiser_task(cfg: MdnsConfig) {
+    // mDNS discovery only supports IPv4 for now; an IPv6 listen address
+    // just doesn't get advertised.
+    let IpAddr::V4(addr) = cfg.addr.ip() else {
+        return;
+    };
+    let port = cfg.addr.port();
+
+    let Ok(sock) = bind() else { return };
+    let instance = format!("{}.{}", cfg.instance, mdns::SERVICE_TYPE);
+    let mut buf = vec![0u8; 512];
+
+    loop {
+        let Ok((n, peer)) = sock.recv_from(&mut buf).await else {
+            return;
+        };
+
+        let Ok(questions) = mdns::parse_questions(&buf[..n]) else {
+            continue;
+        };
+
+        if !questions.iter().any(|q| q == mdns::SERVICE_TYPE) {
+            continue;
+        }
+
+        let resp = mdns::build_response(&instance, &cfg.host, addr, port, RECORD_TTL);
+        let _ = sock.send_to(&resp, peer).await;
+    }
+}
+
+fn bind() -> io::Result<UdpSocket> {
+    let sock = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, MDNS_PORT))?;
+    sock.set_nonblocking(true)?;
+    sock.join_multicast_v4(&MULTICAST_ADDR_V4, &Ipv4Addr::UNSPECIFIED)?;
+    UdpSocket::try_from(sock)
+}
@@ -0,0 +1,261 @@
+use std::cell::RefCell;
+use std::error;
+use std::fmt;
+use std::rc::Rc;
+use std::thread;
+
+use async_channel::{bounded, Receiver, Sender};
+use async_executor::LocalExecutor;
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use postcard::experimental::schema::Schema;
+use postcard_rpc::Key;
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use wb_notifier_driver::bargraph::Bargraph;
+use wb_notifier_driver::lcd::Lcd;
+use wb_notifier_driver::Slot;
+use wb_notifier_proto::*;
+
+use crate::tasks::background::{BlinkInfo, Fault, MarqueeInfo};
+use crate::transport::Transport;
+use crate::{register_handlers, ChanSlot, Context, Dispatch, ErrorLog, PeerFanout, StatusPublish};
+
+/// Topic a command for one of the registered endpoints arrives on, with
+/// the endpoint's own `_PATH` constant appended, e.g. `workbench/rpc/led/set`
+/// carrying the same postcard-encoded `SetLed` a UDP client would send.
+pub const COMMAND_TOPIC_PREFIX: &str = "workbench/rpc/";
+/// Topic the framed reply to a `COMMAND_TOPIC_PREFIX` request is published
+/// back on, same suffix under this prefix instead.
+pub const REPLY_TOPIC_PREFIX: &str = "workbench/rpc/reply/";
+
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Connect(rumqttc::ConnectionError),
+    Client(rumqttc::ClientError),
+    Encode(postcard::Error),
+    /// The publisher thread has exited; nothing more can be sent.
+    Disconnected,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Connect(_) => write!(f, "could not connect to MQTT broker"),
+            Error::Client(_) => write!(f, "MQTT client error"),
+            Error::Encode(_) => write!(f, "could not frame postcard-rpc response"),
+            Error::Disconnected => write!(f, "MQTT publisher thread is gone"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Connect(e) => Some(e),
+            Error::Client(e) => Some(e),
+            Error::Encode(e) => Some(e),
+            Error::Disconnected => None,
+        }
+    }
+}
+
+/// Re-encodes the struct postcard decoded `payload` into as a full
+/// `seq_no`/`key`-framed request buffer, the same shape
+/// `postcard_rpc::headered::to_slice_keyed` already produces for the
+/// UDP/serial paths. `seq_no` is always 0: MQTT has no reply-timeout/retry
+/// loop of its own to disambiguate, just a reply topic per request topic.
+fn reframe<T>(path: &str, payload: &[u8], buf: &mut [u8]) -> Option<usize>
+where
+    T: Schema + Serialize + DeserializeOwned,
+{
+    let value: T = postcard::from_bytes(payload).ok()?;
+    let key = Key::for_path::<T>(path);
+    let used = postcard_rpc::headered::to_slice_keyed(0, key, &value, buf).ok()?;
+    Some(used.len())
+}
+
+/// Maps a `COMMAND_TOPIC_PREFIX`-stripped topic suffix to the endpoint it
+/// names, decodes the postcard payload, and frames it for [`Dispatch`].
+/// Only endpoints [`register_handlers`] actually registers are reachable
+/// here; `led/notify/mirror` and `led/ack/mirror` are peer-to-peer and
+/// deliberately left off the broker-facing topic tree.
+fn frame_command(path: &str, payload: &[u8], buf: &mut [u8]) -> Option<usize> {
+    match path {
+        ECHO_PATH => reframe::<Echo>(path, payload, buf),
+        SET_LED_PATH => reframe::<SetLed>(path, payload, buf),
+        SET_DIMMING_PATH => reframe::<SetDimming>(path, payload, buf),
+        NOTIFY_PATH => reframe::<Notify>(path, payload, buf),
+        CLEAR_NOTIFY_PATH => reframe::<Ack>(path, payload, buf),
+        HD44780_SET_BACKLIGHT_PATH => reframe::<SetBacklight>(path, payload, buf),
+        HD44780_SEND_MSG_PATH => reframe::<SendMsg>(path, payload, buf),
+        SELF_TEST_PATH => reframe::<SelfTest>(path, payload, buf),
+        SET_BLINK_THRESHOLDS_PATH => reframe::<SetBlinkThresholds>(path, payload, buf),
+        LIST_DEVICES_PATH => reframe::<ListDevices>(path, payload, buf),
+        ADD_DEVICE_PATH => reframe::<AddDevice>(path, payload, buf),
+        REMOVE_DEVICE_PATH => reframe::<RemoveDevice>(path, payload, buf),
+        ERROR_QUERY_PATH => reframe::<ErrorQuery>(path, payload, buf),
+        SCHEDULE_NOTIFY_PATH => reframe::<ScheduleNotify>(path, payload, buf),
+        _ => None,
+    }
+}
+
+/// Runs the blocking `rumqttc` client/event loop on its own OS thread and
+/// forwards every raw `(topic, payload)` publish over an `async_channel`,
+/// mirroring how `BlockingEventLoop` keeps the I2C bus off the executor.
+pub(crate) fn spawn_client(
+    cfg: MqttConfig,
+) -> (
+    Receiver<(String, Vec<u8>)>,
+    Sender<(String, Vec<u8>, bool)>,
+) {
+    let (inbound_send, inbound_recv) = bounded(16);
+    let (outbound_send, outbound_recv) = bounded::<(String, Vec<u8>, bool)>(16);
+
+    thread::spawn(move || {
+        let mut opts = MqttOptions::new(cfg.client_id, cfg.host, cfg.port);
+        opts.set_keep_alive(std::time::Duration::from_secs(30));
+
+        let (client, mut conn) = Client::new(opts, 16);
+        let sub_topic = format!("{COMMAND_TOPIC_PREFIX}#");
+        if client.subscribe(sub_topic, QoS::AtLeastOnce).is_err() {
+            return;
+        }
+
+        let publisher = client.clone();
+        thread::spawn(move || {
+            while let Ok((topic, payload, retain)) = outbound_recv.recv_blocking() {
+                let _ = publisher.publish(topic, QoS::AtLeastOnce, retain, payload);
+            }
+        });
+
+        for notification in conn.iter() {
+            let Ok(Event::Incoming(Packet::Publish(publish))) = notification else {
+                continue;
+            };
+
+            let cmd = (publish.topic, publish.payload.to_vec());
+            if inbound_send.send_blocking(cmd).is_err() {
+                break;
+            }
+        }
+    });
+
+    (inbound_recv, outbound_send)
+}
+
+/// Publishes a dispatched handler's reply to the broker instead of a
+/// UDP/serial peer, so a command routed in from [`mqtt_task`] gets its
+/// response on `self.topic`.
+#[derive(Clone)]
+struct MqttTransport {
+    outbound: Sender<(String, Vec<u8>, bool)>,
+    topic: String,
+}
+
+impl Transport for MqttTransport {
+    type Error = Error;
+
+    async fn send_keyed<T>(
+        &self,
+        seq_no: u32,
+        key: Key,
+        payload: &T,
+        buf: &mut [u8],
+    ) -> Result<(), Error>
+    where
+        T: Schema + Serialize,
+    {
+        let used = postcard_rpc::headered::to_slice_keyed(seq_no, key, payload, buf)
+            .map_err(Error::Encode)?;
+
+        // Replies are per-requester, not retained: a late subscriber
+        // shouldn't see a stale response to a request it never made.
+        self.outbound
+            .send((self.topic.clone(), used.to_vec(), false))
+            .await
+            .map_err(|_| Error::Disconnected)
+    }
+}
+
+/// Bridges the MQTT broker to the same [`Dispatch`] table the UDP and
+/// serial front ends drive: a command on `workbench/rpc/<path>` is decoded
+/// and handed to whichever handler [`register_handlers`] registered under
+/// `<path>`, so there's no separate MQTT-only copy of the set-LED/notify/ack
+/// logic to keep in sync. `inbound`/`outbound` come from [`spawn_client`],
+/// called by [`crate::Server::main_loop`] before this task (and the
+/// UDP/serial/SCPI front ends) are spawned, so `status_publish` wraps the
+/// very same `outbound` every front end publishes a retained
+/// `workbench/status/<led>` (or `workbench/status/backlight`) mirror
+/// through, not just commands that arrived over MQTT itself.
+#[allow(clippy::too_many_arguments)]
+pub async fn mqtt_task<I2C, E, D>(
+    ex: &Rc<LocalExecutor<'_>>,
+    inbound: Receiver<(String, Vec<u8>)>,
+    outbound: Sender<(String, Vec<u8>, bool)>,
+    bg: Slot<Bargraph<I2C>>,
+    lcd: Slot<Lcd<I2C, D>>,
+    blink_send: ChanSlot<BlinkInfo>,
+    marquee_send: ChanSlot<MarqueeInfo>,
+    fault_send: Option<Sender<Fault>>,
+    peer_fanout: Option<PeerFanout>,
+    status_publish: Option<StatusPublish>,
+    acquire: Rc<dyn Fn() -> I2C>,
+    device_config: Option<Rc<RefCell<crate::config::DeviceConfig>>>,
+    errors: ErrorLog,
+    schedule_send: crate::ChanSlot<crate::tasks::background::ScheduleInfo>,
+    schedule_next_id: Rc<std::cell::Cell<u64>>,
+    clock: crate::sntp::Clock,
+) where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + 'static,
+    D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+{
+    let mut buf = vec![0u8; 1024];
+
+    let mut dispatch =
+        Dispatch::<Context<'_, '_, I2C, D, MqttTransport>, crate::Error, 16>::new(Context::new(ex));
+    dispatch.context().sensors.bargraph = Some(bg.clone());
+    dispatch.context().sensors.lcd = Some(lcd.clone());
+    dispatch.context().blink_send = blink_send;
+    dispatch.context().marquee_send = marquee_send;
+    dispatch.context().fault_send = fault_send;
+    dispatch.context().peer_fanout = peer_fanout;
+    dispatch.context().status_publish = status_publish;
+    dispatch.context().bus = Some(acquire);
+    dispatch.context().device_config = device_config;
+    dispatch.context().errors = errors;
+    dispatch.context().schedule_send = schedule_send;
+    dispatch.context().schedule_next_id = schedule_next_id;
+    dispatch.context().clock = clock;
+
+    if register_handlers(&mut dispatch).is_err() {
+        return;
+    }
+
+    while let Ok((topic, payload)) = inbound.recv().await {
+        let Some(path) = topic.strip_prefix(COMMAND_TOPIC_PREFIX) else {
+            continue;
+        };
+
+        let Some(n) = frame_command(path, &payload, &mut buf) else {
+            continue;
+        };
+
+        dispatch.context().transport = Some(MqttTransport {
+            outbound: outbound.clone(),
+            topic: format!("{REPLY_TOPIC_PREFIX}{path}"),
+        });
+
+        let _ = dispatch.dispatch(&buf[..n]);
+    }
+}
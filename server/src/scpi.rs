@@ -0,0 +1,636 @@
+use std::cell::RefCell;
+use std::error;
+use std::fmt;
+use std::sync::Arc;
+
+use async_channel::{bounded, Receiver, Sender};
+use async_executor::LocalExecutor;
+use async_lock::Mutex;
+use async_net::TcpListener;
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use futures_lite::{AsyncBufReadExt, AsyncWriteExt};
+use postcard::experimental::schema::Schema;
+use postcard_rpc::Key;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::rc::Rc;
+
+use wb_notifier_driver::bargraph::Bargraph;
+use wb_notifier_driver::lcd::Lcd;
+use wb_notifier_proto::*;
+
+use crate::tasks::background::{BlinkInfo, Fault, MarqueeInfo};
+use crate::transport::Transport;
+use crate::{register_handlers, ChanSlot, Context, Dispatch, ErrorLog, PeerFanout, StatusPublish};
+
+/// A SCPI mnemonic, e.g. `NOTif` accepts both the short form `NOT` and the
+/// long form `NOTIFY`.
+struct Mnemonic {
+    short: &'static str,
+    long: &'static str,
+}
+
+const NOTIFY: Mnemonic = Mnemonic { short: "NOT", long: "NOTIFY" };
+const ACK: Mnemonic = Mnemonic { short: "ACK", long: "ACK" };
+const LED: Mnemonic = Mnemonic { short: "LED", long: "LED" };
+const ALL: Mnemonic = Mnemonic { short: "ALL", long: "ALL" };
+const CONFIG: Mnemonic = Mnemonic { short: "CONF", long: "CONFIG" };
+const BGRAPH: Mnemonic = Mnemonic { short: "BGR", long: "BGRAPH" };
+const DIMMING: Mnemonic = Mnemonic { short: "DIM", long: "DIMMING" };
+const LCD: Mnemonic = Mnemonic { short: "LCD", long: "LCD" };
+const BACKLIGHT: Mnemonic = Mnemonic { short: "BACK", long: "BACKLIGHT" };
+const MSG: Mnemonic = Mnemonic { short: "MSG", long: "MSG" };
+
+fn matches(token: &str, m: &Mnemonic) -> bool {
+    let token = token.to_ascii_uppercase();
+    token.len() >= m.short.len() && m.long.starts_with(token.as_str())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Empty,
+    UnknownCommand(String),
+    BadArgs(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Empty => write!(f, "empty command"),
+            Error::UnknownCommand(c) => write!(f, "unknown command {c:?}"),
+            Error::BadArgs(c) => write!(f, "bad arguments for {c:?}"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+#[derive(Debug)]
+pub enum Command {
+    Idn,
+    Rst,
+    NotifyLed { num: u8, status: Status },
+    NotifyLedQuery { num: u8 },
+    AckLed { num: u8 },
+    AckAll,
+    ConfigBargraphDimming { level: SetDimming },
+    ConfigLcdBacklight { back: SetBacklight },
+    LcdMsg { text: String },
+}
+
+/// Split a line into its `;`-separated commands, trimmed and with empty
+/// ones dropped, the way the SCPI grammar chains multiple commands on one
+/// line. [`split_head_args`] then splits each into (head-path, args).
+fn split_commands(line: &str) -> Vec<&str> {
+    line.split(';').map(str::trim).filter(|s| !s.is_empty()).collect()
+}
+
+fn split_head_args(cmd: &str) -> (&str, &str) {
+    match cmd.find(char::is_whitespace) {
+        Some(idx) => (cmd[..idx].trim(), cmd[idx..].trim()),
+        None => (cmd, ""),
+    }
+}
+
+fn split_args(args: &str) -> Vec<String> {
+    // A quoted string (for LCD:MSG "hello") is kept as a single argument;
+    // everything else is split on commas.
+    if let Some(rest) = args.strip_prefix('"') {
+        if let Some(end) = rest.find('"') {
+            return vec![rest[..end].to_string()];
+        }
+    }
+
+    args.split(',').map(|s| s.trim().to_string()).collect()
+}
+
+fn status_parse(status: &str) -> Result<Status, Error> {
+    match status.to_ascii_uppercase().as_str() {
+        "RED" | "URGENT" | "ERROR" => return Ok(Status::Error),
+        "YELLOW" | "WARNING" => return Ok(Status::Warning),
+        "OK" | "GREEN" | "ON" => return Ok(Status::Ok),
+        _ => {}
+    }
+
+    status
+        .parse::<u16>()
+        .map(|u| if u == 0 { Status::Ok } else { Status::Warning })
+        .map_err(|_| Error::BadArgs(status.to_string()))
+}
+
+fn dimming_parse(level: &str) -> Result<SetDimming, Error> {
+    match level.to_ascii_uppercase().as_str() {
+        "HI" | "HIGH" => Ok(SetDimming::Hi),
+        "LO" | "LOW" => Ok(SetDimming::Lo),
+        _ => Err(Error::BadArgs(level.to_string())),
+    }
+}
+
+fn backlight_parse(state: &str) -> Result<SetBacklight, Error> {
+    match state.to_ascii_uppercase().as_str() {
+        "ON" => Ok(SetBacklight::On),
+        "OFF" => Ok(SetBacklight::Off),
+        _ => Err(Error::BadArgs(state.to_string())),
+    }
+}
+
+/// Parse one SCPI-style command, e.g. `NOTif:LED 3,RED` or `*IDN?`.
+pub fn parse(cmd: &str) -> Result<Command, Error> {
+    let (head, args) = split_head_args(cmd);
+    if head.is_empty() {
+        return Err(Error::Empty);
+    }
+
+    if head == "*IDN?" {
+        return Ok(Command::Idn);
+    }
+    if head == "*RST" {
+        return Ok(Command::Rst);
+    }
+
+    let path: Vec<&str> = head.split(':').collect();
+    let query = path.last().map(|s| s.ends_with('?')).unwrap_or(false);
+    let path: Vec<&str> = path
+        .iter()
+        .map(|s| s.strip_suffix('?').unwrap_or(s))
+        .collect();
+
+    match path.as_slice() {
+        [notify, led] if matches(notify, &NOTIFY) && matches(led, &LED) => {
+            let args = split_args(args);
+            let num: u8 = args
+                .first()
+                .ok_or_else(|| Error::BadArgs(cmd.to_string()))?
+                .parse()
+                .map_err(|_| Error::BadArgs(cmd.to_string()))?;
+
+            if query {
+                return Ok(Command::NotifyLedQuery { num });
+            }
+
+            let status = status_parse(args.get(1).ok_or_else(|| Error::BadArgs(cmd.to_string()))?)?;
+            Ok(Command::NotifyLed { num, status })
+        }
+        [ack, led] if matches(ack, &ACK) && matches(led, &LED) => {
+            let args = split_args(args);
+            let num: u8 = args
+                .first()
+                .ok_or_else(|| Error::BadArgs(cmd.to_string()))?
+                .parse()
+                .map_err(|_| Error::BadArgs(cmd.to_string()))?;
+
+            Ok(Command::AckLed { num })
+        }
+        [ack, all] if matches(ack, &ACK) && matches(all, &ALL) => Ok(Command::AckAll),
+        [config, bgraph, dimming] if matches(config, &CONFIG) && matches(bgraph, &BGRAPH) && matches(dimming, &DIMMING) => {
+            let level = dimming_parse(args).map_err(|_| Error::BadArgs(cmd.to_string()))?;
+            Ok(Command::ConfigBargraphDimming { level })
+        }
+        [config, lcd, backlight] if matches(config, &CONFIG) && matches(lcd, &LCD) && matches(backlight, &BACKLIGHT) => {
+            let back = backlight_parse(args).map_err(|_| Error::BadArgs(cmd.to_string()))?;
+            Ok(Command::ConfigLcdBacklight { back })
+        }
+        [lcd, msg] if matches(lcd, &LCD) && matches(msg, &MSG) => {
+            let text = split_args(args)
+                .into_iter()
+                .next()
+                .ok_or_else(|| Error::BadArgs(cmd.to_string()))?;
+            Ok(Command::LcdMsg { text })
+        }
+        _ => Err(Error::UnknownCommand(cmd.to_string())),
+    }
+}
+
+#[derive(Debug)]
+pub enum ScpiTransportError {
+    Encode(postcard::Error),
+    /// The connection already moved on (or gave up) before the handler
+    /// this was bound to got around to replying.
+    Disconnected,
+}
+
+impl fmt::Display for ScpiTransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScpiTransportError::Encode(_) => write!(f, "could not frame postcard-rpc response"),
+            ScpiTransportError::Disconnected => write!(f, "SCPI connection is gone"),
+        }
+    }
+}
+
+impl error::Error for ScpiTransportError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ScpiTransportError::Encode(e) => Some(e),
+            ScpiTransportError::Disconnected => None,
+        }
+    }
+}
+
+/// Hands a dispatched handler's reply back to the one in-flight SCPI
+/// command waiting on it, instead of a UDP/serial peer: [`run`] holds the
+/// other end of `reply` and decodes the framed bytes back into the
+/// response type it already knows to expect.
+#[derive(Clone)]
+struct ScpiTransport {
+    reply: Sender<Vec<u8>>,
+}
+
+impl Transport for ScpiTransport {
+    type Error = ScpiTransportError;
+
+    async fn send_keyed<T>(
+        &self,
+        seq_no: u32,
+        key: Key,
+        payload: &T,
+        buf: &mut [u8],
+    ) -> Result<(), Self::Error>
+    where
+        T: Schema + Serialize,
+    {
+        let used = postcard_rpc::headered::to_slice_keyed(seq_no, key, payload, buf)
+            .map_err(ScpiTransportError::Encode)?;
+        self.reply
+            .send(used.to_vec())
+            .await
+            .map_err(|_| ScpiTransportError::Disconnected)
+    }
+}
+
+/// Frames `value` as a fresh request on `path` (always `seq_no` 0: SCPI is
+/// one command in, one reply out, with no pipelining of its own to
+/// disambiguate), dispatches it through `dispatch`, and decodes the reply
+/// [`ScpiTransport::send_keyed`] hands back over `reply_recv` into `R`.
+/// `None` on anything going wrong along the way (a request that doesn't
+/// fit `req_buf`, dispatch rejecting the frame, `reply_recv` closing, or a
+/// reply that doesn't decode), which the caller treats the same as a
+/// device error.
+async fn dispatch_and_decode<I2C, E, D, T, R>(
+    dispatch: &mut Dispatch<Context<'_, '_, I2C, D, ScpiTransport>, crate::Error, 16>,
+    reply_recv: &Receiver<Vec<u8>>,
+    path: &str,
+    value: T,
+) -> Option<R>
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + std::error::Error + 'static,
+    D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+    T: Schema + Serialize,
+    R: DeserializeOwned,
+{
+    let mut req_buf = vec![0u8; 256];
+    let key = Key::for_path::<T>(path);
+    let n = postcard_rpc::headered::to_slice_keyed(0, key, &value, &mut req_buf)
+        .ok()?
+        .len();
+
+    dispatch.dispatch(&req_buf[..n]).ok()?;
+
+    let frame = reply_recv.recv().await.ok()?;
+    let (_, body) = postcard_rpc::headered::extract_header_from_bytes(&frame).ok()?;
+    postcard::from_bytes(body).ok()
+}
+
+/// Renders a device-op outcome the same way every other `Command` arm
+/// does: `"OK"`, `"ERR <reason>"`, or `"ERR device"` if no reply could be
+/// had at all (one of [`dispatch_and_decode`]'s `None` cases). An
+/// unconfigured device does reply, as `"ERR device not initialized"`.
+fn format_device_result(resp: Option<Result<(), DeviceError>>) -> String {
+    match resp {
+        Some(Ok(())) => "OK".to_string(),
+        Some(Err(e)) => format!("ERR {e}"),
+        None => "ERR device".to_string(),
+    }
+}
+
+/// Accepts plain-text SCPI sessions on `addr`, one command (or `;`-chained
+/// commands) per line, and applies them to the same bargraph/LCD sensors
+/// the postcard path drives, through the very same [`register_handlers`]
+/// table UDP/MQTT dispatch into - so e.g. a notify issued over SCPI still
+/// drives the blink escalation and peer mirroring a `NotifyEndpoint`
+/// request would, and (when `status_publish` is `Some`) the same retained
+/// `workbench/status/<led>` MQTT mirror too. Only `bg`/`lcd` are a
+/// one-time snapshot of the sensors configured at startup, so a device
+/// added later via `AddDeviceEndpoint` won't show up here until the
+/// daemon restarts.
+#[allow(clippy::too_many_arguments)]
+pub async fn scpi_task<I2C, E, D>(
+    ex: Rc<LocalExecutor<'_>>,
+    addr: async_net::SocketAddr,
+    bg: Option<Arc<Mutex<Bargraph<I2C>>>>,
+    lcd: Option<Arc<Mutex<Lcd<I2C, D>>>>,
+    blink_send: ChanSlot<BlinkInfo>,
+    marquee_send: ChanSlot<MarqueeInfo>,
+    fault_send: Option<Sender<Fault>>,
+    peer_fanout: Option<PeerFanout>,
+    status_publish: Option<StatusPublish>,
+    errors: ErrorLog,
+    schedule_send: ChanSlot<crate::tasks::background::ScheduleInfo>,
+    schedule_next_id: Rc<std::cell::Cell<u64>>,
+    clock: crate::sntp::Clock,
+) -> std::io::Result<()>
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + std::error::Error + 'static,
+    D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+{
+    let listener = TcpListener::bind(addr).await?;
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let conn_ex = ex.clone();
+        let bg = bg.clone();
+        let lcd = lcd.clone();
+        let blink_send = blink_send.clone();
+        let marquee_send = marquee_send.clone();
+        let fault_send = fault_send.clone();
+        let peer_fanout = peer_fanout.clone();
+        let status_publish = status_publish.clone();
+        let errors = errors.clone();
+        let schedule_send = schedule_send.clone();
+        let schedule_next_id = schedule_next_id.clone();
+        let clock = clock.clone();
+
+        ex.spawn(async move {
+            let _ = handle_conn(
+                conn_ex,
+                stream,
+                bg,
+                lcd,
+                blink_send,
+                marquee_send,
+                fault_send,
+                peer_fanout,
+                status_publish,
+                errors,
+                schedule_send,
+                schedule_next_id,
+                clock,
+            )
+            .await;
+        })
+        .detach();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_conn<I2C, E, D>(
+    ex: Rc<LocalExecutor<'_>>,
+    stream: async_net::TcpStream,
+    bg: Option<Arc<Mutex<Bargraph<I2C>>>>,
+    lcd: Option<Arc<Mutex<Lcd<I2C, D>>>>,
+    blink_send: ChanSlot<BlinkInfo>,
+    marquee_send: ChanSlot<MarqueeInfo>,
+    fault_send: Option<Sender<Fault>>,
+    peer_fanout: Option<PeerFanout>,
+    status_publish: Option<StatusPublish>,
+    errors: ErrorLog,
+    schedule_send: ChanSlot<crate::tasks::background::ScheduleInfo>,
+    schedule_next_id: Rc<std::cell::Cell<u64>>,
+    clock: crate::sntp::Clock,
+) -> std::io::Result<()>
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + std::error::Error + 'static,
+    D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+{
+    let mut dispatch =
+        Dispatch::<Context<'_, '_, I2C, D, ScpiTransport>, crate::Error, 16>::new(Context::new(&ex));
+    // `bg`/`lcd` are a fixed startup snapshot rather than the live
+    // `wb_notifier_driver::Slot`s, so these never change after being set
+    // here; see `scpi_task`'s doc comment.
+    dispatch.context().sensors.bargraph = Some(Rc::new(RefCell::new(bg.clone())));
+    dispatch.context().sensors.lcd = Some(Rc::new(RefCell::new(lcd.clone())));
+    dispatch.context().blink_send = blink_send;
+    dispatch.context().marquee_send = marquee_send;
+    dispatch.context().fault_send = fault_send;
+    dispatch.context().peer_fanout = peer_fanout;
+    dispatch.context().status_publish = status_publish;
+    dispatch.context().errors = errors;
+    dispatch.context().schedule_send = schedule_send;
+    dispatch.context().schedule_next_id = schedule_next_id;
+    dispatch.context().clock = clock;
+
+    if register_handlers(&mut dispatch).is_err() {
+        return Ok(());
+    }
+
+    let (reply_send, reply_recv) = bounded(1);
+
+    let mut reader = futures_lite::io::BufReader::new(stream.clone());
+    let mut writer = stream;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+
+        for cmd in split_commands(&line) {
+            let reply = match parse(cmd) {
+                Ok(cmd) => {
+                    dispatch.context().transport = Some(ScpiTransport {
+                        reply: reply_send.clone(),
+                    });
+                    run(cmd, &mut dispatch, &reply_recv, &bg).await
+                }
+                Err(e) => format!("ERR {e}"),
+            };
+
+            writer.write_all(reply.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+        }
+    }
+}
+
+async fn run<I2C, E, D>(
+    cmd: Command,
+    dispatch: &mut Dispatch<Context<'_, '_, I2C, D, ScpiTransport>, crate::Error, 16>,
+    reply_recv: &Receiver<Vec<u8>>,
+    bg: &Option<Arc<Mutex<Bargraph<I2C>>>>,
+) -> String
+where
+    I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+    E: Send + std::error::Error + 'static,
+    D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+{
+    match cmd {
+        Command::Idn => format!("{},{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        Command::Rst => {
+            let resp = dispatch_and_decode::<_, E, _, Ack, AckResponse>(
+                dispatch,
+                reply_recv,
+                CLEAR_NOTIFY_PATH,
+                Ack { num: None },
+            )
+            .await;
+
+            format_device_result(resp.map(|r| r.0))
+        }
+        Command::NotifyLed { num, status } => {
+            let resp = dispatch_and_decode::<_, E, _, Notify, NotifyResponse>(
+                dispatch,
+                reply_recv,
+                NOTIFY_PATH,
+                Notify { num, status, msg: None },
+            )
+            .await;
+
+            format_device_result(resp.map(|r| r.0))
+        }
+        Command::NotifyLedQuery { num } => {
+            // There's no `NotifyLedQuery` endpoint on the postcard side to
+            // route through, so this reads the bargraph's cached display
+            // buffer directly, the same way `Bargraph::self_test` restores
+            // it afterwards.
+            let Some(bg) = bg.clone() else {
+                return "ERR no bargraph configured".to_string();
+            };
+
+            match blocking::unblock(move || bg.lock_arc_blocking().led_color(num)).await {
+                Some(color) => format!("{color:?}"),
+                None => format!(
+                    "ERR {}",
+                    DeviceError::OutOfRange {
+                        num,
+                        max: wb_notifier_driver::bargraph::NUM_LEDS - 1
+                    }
+                ),
+            }
+        }
+        Command::AckLed { num } => {
+            let resp = dispatch_and_decode::<_, E, _, Ack, AckResponse>(
+                dispatch,
+                reply_recv,
+                CLEAR_NOTIFY_PATH,
+                Ack { num: Some(num) },
+            )
+            .await;
+
+            format_device_result(resp.map(|r| r.0))
+        }
+        Command::AckAll => {
+            let resp = dispatch_and_decode::<_, E, _, Ack, AckResponse>(
+                dispatch,
+                reply_recv,
+                CLEAR_NOTIFY_PATH,
+                Ack { num: None },
+            )
+            .await;
+
+            format_device_result(resp.map(|r| r.0))
+        }
+        Command::ConfigBargraphDimming { level } => {
+            let resp = dispatch_and_decode::<_, E, _, SetDimming, SetDimmingResponse>(
+                dispatch,
+                reply_recv,
+                SET_DIMMING_PATH,
+                level,
+            )
+            .await;
+
+            format_device_result(resp.map(|r| r.0))
+        }
+        Command::ConfigLcdBacklight { back } => {
+            let resp = dispatch_and_decode::<_, E, _, SetBacklight, SetBacklightResponse>(
+                dispatch,
+                reply_recv,
+                HD44780_SET_BACKLIGHT_PATH,
+                back,
+            )
+            .await;
+
+            format_device_result(resp.map(|r| r.0))
+        }
+        Command::LcdMsg { text } => {
+            let resp = dispatch_and_decode::<_, E, _, SendMsg, SendMsgResponse>(
+                dispatch,
+                reply_recv,
+                HD44780_SEND_MSG_PATH,
+                SendMsg(text),
+            )
+            .await;
+
+            match resp.map(|r| r.0) {
+                Some(Ok(MsgStatus::Ok)) => "OK".to_string(),
+                Some(Ok(MsgStatus::Truncated)) => "OK TRUNCATED".to_string(),
+                Some(Err(e)) => format!("ERR {e}"),
+                None => "ERR device".to_string(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_commands_splits_on_semicolons_and_trims() {
+        assert_eq!(
+            split_commands("NOT:LED 3,RED ; ACK:LED 3 ;  "),
+            vec!["NOT:LED 3,RED", "ACK:LED 3"]
+        );
+    }
+
+    #[test]
+    fn split_commands_drops_empty_segments() {
+        assert_eq!(split_commands(";;"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn parse_accepts_short_and_long_mnemonics() {
+        assert!(matches!(
+            parse("NOT:LED 3,RED"),
+            Ok(Command::NotifyLed {
+                num: 3,
+                status: Status::Error
+            })
+        ));
+        assert!(matches!(
+            parse("NOTIFY:LED 3,RED"),
+            Ok(Command::NotifyLed {
+                num: 3,
+                status: Status::Error
+            })
+        ));
+    }
+
+    #[test]
+    fn parse_query_suffix_selects_the_query_variant() {
+        assert!(matches!(
+            parse("NOT:LED? 3"),
+            Ok(Command::NotifyLedQuery { num: 3 })
+        ));
+    }
+
+    #[test]
+    fn parse_star_commands() {
+        assert!(matches!(parse("*IDN?"), Ok(Command::Idn)));
+        assert!(matches!(parse("*RST"), Ok(Command::Rst)));
+    }
+
+    #[test]
+    fn parse_rejects_unknown_command() {
+        assert!(matches!(
+            parse("BOGUS:THING"),
+            Err(Error::UnknownCommand(_))
+        ));
+    }
+
+    #[test]
+    fn parse_rejects_empty_command() {
+        assert!(matches!(parse(""), Err(Error::Empty)));
+    }
+
+    #[test]
+    fn parse_rejects_bad_args() {
+        assert!(matches!(
+            parse("NOT:LED notanumber,RED"),
+            Err(Error::BadArgs(_))
+        ));
+    }
+}
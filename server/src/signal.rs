@@ -0,0 +1,85 @@
+//! SIGINT/SIGTERM/SIGHUP handling for the smol-based server. Compiles out
+//! entirely on platforms without Unix signals, in which case
+//! [`wait_for_signal`] never resolves and the server just runs forever.
+
+/// What [`wait_for_signal`] woke up for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalEvent {
+    /// SIGINT or SIGTERM: run the shutdown sequence and exit.
+    Shutdown,
+    /// SIGHUP: re-open the I2C bus and re-initialize whatever's
+    /// configured, to recover a wedged device or pick up a changed bus
+    /// path/address without restarting the daemon.
+    Reload,
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::SignalEvent;
+    use async_signal::{Signal, Signals};
+    use futures_lite::StreamExt;
+    use std::io;
+
+    /// Waits for the next SIGINT/SIGTERM/SIGHUP, whichever comes first.
+    pub async fn wait_for_signal() -> io::Result<SignalEvent> {
+        let mut signals = Signals::new([Signal::Int, Signal::Term, Signal::Hup])?;
+
+        loop {
+            match signals.next().await {
+                Some(Ok(Signal::Hup)) => return Ok(SignalEvent::Reload),
+                Some(Ok(_)) => return Ok(SignalEvent::Shutdown),
+                Some(Err(e)) => return Err(e),
+                // The signal stream doesn't end on its own; treat it
+                // ending at all the same as a request to shut down.
+                None => return Ok(SignalEvent::Shutdown),
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::SignalEvent;
+    use std::future::pending;
+    use std::io;
+
+    pub async fn wait_for_signal() -> io::Result<SignalEvent> {
+        pending().await
+    }
+}
+
+pub use imp::wait_for_signal;
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// Exercises the exact function `serve_udp`/`serve_serial` race
+    /// against every request against: raise the real OS signal against
+    /// this test process and confirm it's classified the way the live
+    /// server's shutdown/reload handling expects. (`main_loop` itself
+    /// can't be driven end-to-end here since it unconditionally opens a
+    /// real `/dev/i2c-*` device with no hook for a fake bus, so this is
+    /// the closest thing to the real entry point this sandbox can run.)
+    #[test]
+    fn sighup_is_reload() {
+        futures_lite::future::block_on(async {
+            // Poll once first: `wait_for_signal` only registers its
+            // handlers when its body starts running.
+            let mut wait = std::pin::pin!(wait_for_signal());
+            assert!(futures_lite::future::poll_once(&mut wait).await.is_none());
+            unsafe { libc::raise(libc::SIGHUP) };
+            assert_eq!(wait.await.unwrap(), SignalEvent::Reload);
+        });
+    }
+
+    #[test]
+    fn sigint_is_shutdown() {
+        futures_lite::future::block_on(async {
+            let mut wait = std::pin::pin!(wait_for_signal());
+            assert!(futures_lite::future::poll_once(&mut wait).await.is_none());
+            unsafe { libc::raise(libc::SIGINT) };
+            assert_eq!(wait.await.unwrap(), SignalEvent::Shutdown);
+        });
+    }
+}
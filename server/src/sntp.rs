@@ -0,0 +1,136 @@
+//! Minimal RFC 4330 SNTP client. Gives [`tasks::background::schedule`] a
+//! wall-clock reference for `Schedule::At { epoch_secs }`: the hardware
+//! this daemon runs on has no battery-backed RTC, so its local clock is
+//! whatever it happened to boot with until something sets it.
+//!
+//! [`tasks::background::schedule`]: crate::tasks::background::schedule
+
+use std::cell::Cell;
+use std::error;
+use std::fmt;
+use std::io;
+use std::net::UdpSocket;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_io::Timer;
+use blocking::unblock;
+
+const NTP_PORT: u16 = 123;
+/// Seconds between the NTP epoch (1900-01-01) and the Unix epoch
+/// (1970-01-01), subtracted from a server timestamp to land on
+/// [`SystemTime::now`]'s own reference point.
+const NTP_UNIX_EPOCH_DELTA: u64 = 2_208_988_800;
+/// How often to re-query the server once synced, so a local clock that's
+/// merely fast or slow (rather than wrong) doesn't drift the offset too
+/// far out of date between queries.
+const QUERY_INTERVAL: Duration = Duration::from_secs(3600);
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug)]
+pub struct SntpConfig {
+    pub server: String,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The server never replied within [`QUERY_TIMEOUT`].
+    NoReply,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(_) => write!(f, "io error querying SNTP server"),
+            Error::NoReply => write!(f, "SNTP server did not reply in time"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::NoReply => None,
+        }
+    }
+}
+
+/// How far `SystemTime::now()` is from the real time, as last measured by
+/// [`sntp_task`]: `server_unix - system_unix`, kept signed rather than a
+/// `Duration` so a local clock running fast doesn't get silently clamped
+/// to "no offset". `None` until the first successful query.
+#[derive(Clone, Default)]
+pub struct Clock {
+    offset: Rc<Cell<Option<i64>>>,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The current wall-clock time corrected by the last measured offset,
+    /// or `None` if [`sntp_task`] hasn't synced yet.
+    pub fn now_unix(&self) -> Option<u64> {
+        let offset = self.offset.get()?;
+        let system_unix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
+        Some((system_unix + offset).max(0) as u64)
+    }
+}
+
+/// Re-queries `cfg.server` every [`QUERY_INTERVAL`], updating `clock`'s
+/// offset on success. A failed query leaves the previous offset in place
+/// rather than reverting to unsynced, since a stale estimate is still
+/// better than none.
+pub async fn sntp_task(cfg: SntpConfig, clock: Clock) {
+    loop {
+        let server = cfg.server.clone();
+        if let Ok(offset) = unblock(move || query(&server)).await {
+            clock.offset.set(Some(offset));
+        }
+
+        Timer::after(QUERY_INTERVAL).await;
+    }
+}
+
+/// Sends a minimal SNTP client request and returns the signed offset
+/// between the server's clock and ours. Blocking (plain `std::net`), so
+/// this only ever runs inside [`blocking::unblock`].
+fn query(server: &str) -> Result<i64, Error> {
+    let sock = UdpSocket::bind("0.0.0.0:0").map_err(Error::Io)?;
+    sock.set_read_timeout(Some(QUERY_TIMEOUT)).map_err(Error::Io)?;
+    sock.connect((server, NTP_PORT)).map_err(Error::Io)?;
+
+    // LI=0 (no warning), VN=4, Mode=3 (client); the rest of the 48-byte
+    // packet is left zeroed, which RFC 4330 allows for a minimal request.
+    let mut packet = [0u8; 48];
+    packet[0] = 0b00_100_011;
+    sock.send(&packet).map_err(Error::Io)?;
+
+    let system_unix = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    let mut reply = [0u8; 48];
+    let n = sock.recv(&mut reply).map_err(|e| match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Error::NoReply,
+        _ => Error::Io(e),
+    })?;
+    if n < 48 {
+        return Err(Error::NoReply);
+    }
+
+    // Transmit timestamp: seconds since the NTP epoch, big-endian, at
+    // bytes 40..44. The fractional-second field right after it is more
+    // precision than an LED bargraph's schedule needs, so it's ignored.
+    let server_secs_since_1900 = u32::from_be_bytes(reply[40..44].try_into().unwrap()) as u64;
+    let server_unix = server_secs_since_1900.saturating_sub(NTP_UNIX_EPOCH_DELTA);
+
+    Ok(server_unix as i64 - system_unix.as_secs() as i64)
+}
@@ -1,7 +1,6 @@
-use async_channel::{bounded, Sender};
+use async_channel::Sender;
 use async_executor::LocalExecutor;
 use async_lock::Mutex;
-use async_net::{SocketAddr, UdpSocket};
 use blocking::unblock;
 use embedded_hal::blocking::i2c::{Write, WriteRead};
 use postcard_rpc::{self, Key};
@@ -11,53 +10,92 @@ use std::sync::Arc;
 use wb_notifier_driver::bargraph;
 use wb_notifier_driver::lcd;
 
+use crate::transport::Transport;
 use wb_notifier_driver;
 use wb_notifier_proto::*;
 
 pub(super) mod handlers {
     use super::*;
-    use background::BlinkInfo;
+    use background::{BlinkInfo, Fault};
     use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+    use std::cell::RefCell;
+    use std::time::{Duration, Instant};
+    use wb_notifier_proto::{AddDevice, ConfigError, ListDevices, RemoveDevice};
 
-    pub async fn set_led<'a, I2C, E>(
+    /// Maps a failed bargraph LED op onto the wire-level [`DeviceError`],
+    /// filling in the range that was actually violated.
+    fn led_error<E>(err: bargraph::Error<E>, num: u8) -> DeviceError
+    where
+        E: std::error::Error + 'static,
+    {
+        let reason = err.abort_reason();
+
+        match err {
+            bargraph::Error::OutOfRange => DeviceError::OutOfRange {
+                num,
+                max: bargraph::NUM_LEDS - 1,
+            },
+            bargraph::Error::Hal(_) => DeviceError::I2cBus(reason.unwrap_or(AbortReason::Other)),
+        }
+    }
+
+    /// Fire-and-forget: tell [`background::reconnect`] about a bus fault so
+    /// it can start retrying, without making the handler wait on it.
+    fn report_fault(fault_send: &Option<Sender<Fault>>, err: DeviceError, fault: Fault) {
+        if matches!(err, DeviceError::I2cBus(_)) {
+            if let Some(fault_send) = fault_send {
+                let _ = fault_send.try_send(fault);
+            }
+        }
+    }
+
+    pub async fn set_led<'a, I2C, E, X>(
         _ex: Rc<LocalExecutor<'_>>,
         seq_no: u32,
         key: Key,
-        (sock, addr): (UdpSocket, SocketAddr),
-        bg: Arc<Mutex<bargraph::Bargraph<I2C>>>,
+        transport: X,
+        fault_send: Option<Sender<Fault>>,
+        errors: crate::ErrorLog,
+        bg: Option<Arc<Mutex<bargraph::Bargraph<I2C>>>>,
         SetLed { num, color }: SetLed,
     ) where
         I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
-        E: Send + 'static,
+        E: Send + std::error::Error + 'static,
+        X: Transport,
     {
         let mut buf = vec![0u8; 1024];
 
-        // For now, we give up on any send/recv/downcast/deserialize errors and
-        // rely on client to time out.
-        let bg = bg.clone();
-        let res = unblock(move || bg.lock_arc_blocking().set_led_no(num, color)).await;
-
-        let resp_res = if res.is_ok() {
-            SetLedResponse(Ok(()))
-        } else {
-            SetLedResponse(Err(RequestError {}))
+        let resp_res = match bg {
+            Some(bg) => {
+                // For now, we give up on any send/recv/downcast/deserialize
+                // errors and rely on client to time out.
+                let res = bargraph::Bargraph::set_led_no_async(bg, num, color).await;
+                SetLedResponse(res.map_err(|e| led_error(e, num)))
+            }
+            None => SetLedResponse(Err(DeviceError::NotInitialized)),
         };
 
-        if let Ok(used) = postcard_rpc::headered::to_slice_keyed(seq_no, key, &resp_res, &mut buf) {
-            let _ = sock.send_to(used, addr).await;
+        if let Err(e) = resp_res.0 {
+            report_fault(&fault_send, e, Fault::Bargraph);
+            crate::record_error(&errors, seq_no, key, DispatchError::Device(e));
         }
+
+        let _ = transport.send_keyed(seq_no, key, &resp_res, &mut buf).await;
     }
 
-    pub async fn set_dimming<'a, I2C, E>(
+    pub async fn set_dimming<'a, I2C, E, X>(
         _ex: Rc<LocalExecutor<'_>>,
         seq_no: u32,
         key: Key,
-        (sock, addr): (UdpSocket, SocketAddr),
-        bg: Arc<Mutex<bargraph::Bargraph<I2C>>>,
+        transport: X,
+        fault_send: Option<Sender<Fault>>,
+        errors: crate::ErrorLog,
+        bg: Option<Arc<Mutex<bargraph::Bargraph<I2C>>>>,
         dimming: SetDimming,
     ) where
         I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
-        E: Send + 'static,
+        E: Send + std::error::Error + 'static,
+        X: Transport,
     {
         let mut buf = vec![0u8; 1024];
 
@@ -66,162 +104,626 @@ pub(super) mod handlers {
             SetDimming::Lo => bargraph::Dimming::BRIGHTNESS_3_16,
         };
 
-        // For now, we give up on any send/recv/downcast/deserialize errors and
-        // rely on client to time out.
-        let bg = bg.clone();
-        let res = unblock(move || bg.lock_arc_blocking().set_dimming(req)).await;
+        let resp_res = match bg {
+            Some(bg) => {
+                // For now, we give up on any send/recv/downcast/deserialize
+                // errors and rely on client to time out.
+                let res = bargraph::Bargraph::set_dimming_async(bg, req).await;
 
-        let resp_res = if res.is_ok() {
-            SetDimmingResponse(Ok(()))
-        } else {
-            SetDimmingResponse(Err(RequestError {}))
+                // `set_dimming` can only fail on the HAL transaction, never
+                // with `OutOfRange`.
+                SetDimmingResponse(res.map_err(|e| {
+                    DeviceError::I2cBus(e.abort_reason().unwrap_or(AbortReason::Other))
+                }))
+            }
+            None => SetDimmingResponse(Err(DeviceError::NotInitialized)),
         };
 
-        if let Ok(used) = postcard_rpc::headered::to_slice_keyed(seq_no, key, &resp_res, &mut buf) {
-            let _ = sock.send_to(used, addr).await;
+        if let Err(e) = resp_res.0 {
+            report_fault(&fault_send, e, Fault::Bargraph);
+            crate::record_error(&errors, seq_no, key, DispatchError::Device(e));
         }
+
+        let _ = transport.send_keyed(seq_no, key, &resp_res, &mut buf).await;
     }
 
-    pub async fn notify<'a, I2C, E>(
+    /// Reconfigures how long an LED dwells at each blink rate before
+    /// escalating to the next one, against the live
+    /// [`background::blink`] worker (see [`background::BlinkThresholds`])
+    /// instead of its previously-hardcoded 60s/300s stages.
+    pub async fn set_blink_thresholds<'a, X>(
         _ex: Rc<LocalExecutor<'_>>,
         seq_no: u32,
         key: Key,
-        (sock, addr): (UdpSocket, SocketAddr),
-        blink_send: Sender<BlinkInfo>,
-        bg: Arc<Mutex<bargraph::Bargraph<I2C>>>,
-        Notify { num, status }: Notify,
+        transport: X,
+        blink_send: Option<Sender<BlinkInfo>>,
+        thresholds: SetBlinkThresholds,
+    ) where
+        X: Transport,
+    {
+        let mut buf = vec![0u8; 1024];
+
+        let resp_res = match &blink_send {
+            Some(blink_send) => {
+                let _ = blink_send
+                    .send(BlinkInfo::SetThresholds(thresholds.into()))
+                    .await;
+                SetBlinkThresholdsResponse(Ok(()))
+            }
+            // No bargraph configured, so there's no escalation worker to
+            // reconfigure.
+            None => SetBlinkThresholdsResponse(Err(RequestError {})),
+        };
+
+        let _ = transport.send_keyed(seq_no, key, &resp_res, &mut buf).await;
+    }
+
+    pub async fn self_test<'a, I2C, E, X>(
+        _ex: Rc<LocalExecutor<'_>>,
+        seq_no: u32,
+        key: Key,
+        transport: X,
+        fault_send: Option<Sender<Fault>>,
+        errors: crate::ErrorLog,
+        bg: Option<Arc<Mutex<bargraph::Bargraph<I2C>>>>,
+        SelfTest {}: SelfTest,
+    ) where
+        I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+        E: Send + std::error::Error + 'static,
+        X: Transport,
+    {
+        let mut buf = vec![0u8; 1024];
+
+        let resp_res = match bg {
+            Some(bg) => {
+                // For now, we give up on any send/recv/downcast/deserialize
+                // errors and rely on client to time out.
+                let res = bargraph::Bargraph::self_test_async(bg).await;
+
+                // `self_test` only ever drives LEDs/dimming/blink it already
+                // knows are in range, so a failure can only be a HAL
+                // transaction error.
+                SelfTestResponse(res.map_err(|e| {
+                    DeviceError::I2cBus(e.abort_reason().unwrap_or(AbortReason::Other))
+                }))
+            }
+            None => SelfTestResponse(Err(DeviceError::NotInitialized)),
+        };
+
+        if let Err(e) = resp_res.0 {
+            report_fault(&fault_send, e, Fault::Bargraph);
+            crate::record_error(&errors, seq_no, key, DispatchError::Device(e));
+        }
+
+        let _ = transport.send_keyed(seq_no, key, &resp_res, &mut buf).await;
+    }
+
+    pub async fn notify<'a, I2C, E, X>(
+        ex: Rc<LocalExecutor<'_>>,
+        seq_no: u32,
+        key: Key,
+        transport: X,
+        fault_send: Option<Sender<Fault>>,
+        errors: crate::ErrorLog,
+        blink_send: Option<Sender<BlinkInfo>>,
+        marquee_send: Option<Sender<background::MarqueeInfo>>,
+        peer_fanout: Option<crate::PeerFanout>,
+        status_publish: Option<crate::StatusPublish>,
+        from_peer: bool,
+        bg: Option<Arc<Mutex<bargraph::Bargraph<I2C>>>>,
+        Notify { num, status, msg }: Notify,
     ) where
         I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
-        E: Send + 'static,
+        E: Send + std::error::Error + 'static,
+        X: Transport,
     {
         let mut buf = vec![0u8; 1024];
 
-        let color = match status {
+        let color = match &status {
             Status::Ok => LedColor::Green,
             Status::Warning => LedColor::Yellow,
             Status::Error => LedColor::Red,
         };
 
+        let Some(bg) = bg else {
+            let resp_res = NotifyResponse(Err(DeviceError::NotInitialized));
+            crate::record_error(
+                &errors,
+                seq_no,
+                key,
+                DispatchError::Device(DeviceError::NotInitialized),
+            );
+            let _ = transport.send_keyed(seq_no, key, &resp_res, &mut buf).await;
+            return;
+        };
+
         // For now, we give up on any send/recv/cl/deserialize errors and
         // rely on client to time out.
-        let bg = bg.clone();
-        let res = unblock(move || bg.lock_arc_blocking().set_led_no(num, color)).await;
+        let res = bargraph::Bargraph::set_led_no_async(bg, num, color).await;
 
-        let resp_res = if res.is_ok() {
-            NotifyResponse(Ok(()))
-        } else {
-            NotifyResponse(Err(RequestError {}))
-        };
+        let resp_res = NotifyResponse(res.map_err(|e| led_error(e, num)));
+        if let Err(e) = resp_res.0 {
+            report_fault(&fault_send, e, Fault::Bargraph);
+            crate::record_error(&errors, seq_no, key, DispatchError::Device(e));
+        }
+        if resp_res.0.is_ok() {
+            if let Some(peer_fanout) = &peer_fanout {
+                let key = peer_fanout.notify_key;
+                peer_fanout.mirror(
+                    &ex,
+                    from_peer,
+                    transport.peer_addr(),
+                    seq_no,
+                    key,
+                    Notify {
+                        num,
+                        status,
+                        msg: msg.clone(),
+                    },
+                );
+            }
+
+            if let Some(status_publish) = &status_publish {
+                status_publish
+                    .publish(num, crate::status_payload(status))
+                    .await;
+            }
 
-        let _ = blink_send.send(BlinkInfo::LedSet).await;
-        if let Ok(used) = postcard_rpc::headered::to_slice_keyed(seq_no, key, &resp_res, &mut buf) {
-            let _ = sock.send_to(used, addr).await;
+            if let (Some(marquee_send), Some(text)) = (&marquee_send, msg) {
+                let _ = marquee_send
+                    .send(background::MarqueeInfo::Show { num, text })
+                    .await;
+            }
         }
+
+        if let Some(blink_send) = &blink_send {
+            let _ = blink_send.send(BlinkInfo::LedSet { num }).await;
+        }
+        let _ = transport.send_keyed(seq_no, key, &resp_res, &mut buf).await;
     }
 
-    pub async fn ack<'a, I2C, E>(
-        _ex: Rc<LocalExecutor<'_>>,
+    pub async fn ack<'a, I2C, E, X>(
+        ex: Rc<LocalExecutor<'_>>,
         seq_no: u32,
         key: Key,
-        (sock, addr): (UdpSocket, SocketAddr),
-        blink_send: Sender<BlinkInfo>,
-        bg: Arc<Mutex<bargraph::Bargraph<I2C>>>,
+        transport: X,
+        fault_send: Option<Sender<Fault>>,
+        errors: crate::ErrorLog,
+        blink_send: Option<Sender<BlinkInfo>>,
+        marquee_send: Option<Sender<background::MarqueeInfo>>,
+        peer_fanout: Option<crate::PeerFanout>,
+        status_publish: Option<crate::StatusPublish>,
+        from_peer: bool,
+        bg: Option<Arc<Mutex<bargraph::Bargraph<I2C>>>>,
         Ack { num }: Ack,
     ) where
         I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
-        E: Send + 'static,
+        E: Send + std::error::Error + 'static,
+        X: Transport,
     {
         let mut buf = vec![0u8; 1024];
+
+        let Some(bg) = bg else {
+            let resp_res = AckResponse(Err(DeviceError::NotInitialized));
+            crate::record_error(
+                &errors,
+                seq_no,
+                key,
+                DispatchError::Device(DeviceError::NotInitialized),
+            );
+            let _ = transport.send_keyed(seq_no, key, &resp_res, &mut buf).await;
+            return;
+        };
+
         // For now, we give up on any send/recv/downcast/deserialize errors and
         // rely on client to time out.
-
         let resp_res;
         match num {
             Some(num) => {
                 let bg = bg.clone();
-                let res =
-                    unblock(move || bg.lock_arc_blocking().set_led_no(num, LedColor::Off)).await;
+                let res = bargraph::Bargraph::set_led_no_async(bg, num, LedColor::Off).await;
 
-                resp_res = if res.is_ok() {
-                    AckResponse(Ok(()))
-                } else {
-                    AckResponse(Err(RequestError {}))
-                };
+                resp_res = AckResponse(res.map_err(|e| led_error(e, num)));
+
+                if let Some(blink_send) = &blink_send {
+                    let _ = blink_send.send(BlinkInfo::LedClear { num }).await;
+                }
             }
             None => {
                 let bg = bg.clone();
-                let res = unblock(move || bg.lock_arc_blocking().clear_all()).await;
+                let res = bargraph::Bargraph::clear_all_async(bg).await;
 
-                resp_res = if res.is_ok() {
-                    AckResponse(Ok(()))
-                } else {
-                    AckResponse(Err(RequestError {}))
-                };
+                // `clear_all` can only fail on the HAL transaction, never
+                // with `OutOfRange`.
+                resp_res = AckResponse(res.map_err(|e| {
+                    DeviceError::I2cBus(e.abort_reason().unwrap_or(AbortReason::Other))
+                }));
+
+                if let Some(blink_send) = &blink_send {
+                    let _ = blink_send.send(BlinkInfo::ClearAll).await;
+                }
             }
         }
+        if let Err(e) = resp_res.0 {
+            report_fault(&fault_send, e, Fault::Bargraph);
+            crate::record_error(&errors, seq_no, key, DispatchError::Device(e));
+        }
+        if resp_res.0.is_ok() {
+            if let Some(peer_fanout) = &peer_fanout {
+                let key = peer_fanout.ack_key;
+                peer_fanout.mirror(&ex, from_peer, transport.peer_addr(), seq_no, key, Ack { num });
+            }
 
-        let _ = blink_send.send(BlinkInfo::LedClear).await;
-        if let Ok(used) = postcard_rpc::headered::to_slice_keyed(seq_no, key, &resp_res, &mut buf) {
-            let _ = sock.send_to(used, addr).await;
+            if let (Some(status_publish), Some(num)) = (&status_publish, num) {
+                status_publish.publish(num, b"ok".to_vec()).await;
+            }
+
+            if let Some(marquee_send) = &marquee_send {
+                let info = match num {
+                    Some(num) => background::MarqueeInfo::Clear { num },
+                    None => background::MarqueeInfo::ClearAll,
+                };
+                let _ = marquee_send.send(info).await;
+            }
         }
+
+        let _ = transport.send_keyed(seq_no, key, &resp_res, &mut buf).await;
     }
 
-    pub async fn set_backlight<'a, I2C, E, D>(
+    pub async fn set_backlight<'a, I2C, E, D, X>(
         _ex: Rc<LocalExecutor<'_>>,
         seq_no: u32,
         key: Key,
-        (sock, addr): (UdpSocket, SocketAddr),
-        lcd: Arc<Mutex<lcd::Lcd<I2C, D>>>,
+        transport: X,
+        fault_send: Option<Sender<Fault>>,
+        errors: crate::ErrorLog,
+        status_publish: Option<crate::StatusPublish>,
+        lcd: Option<Arc<Mutex<lcd::Lcd<I2C, D>>>>,
         backlight: SetBacklight,
     ) where
         I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
-        E: Send + 'static,
-        D: DelayMs<u8> + DelayUs<u16> + Send + 'static
+        E: Send + std::error::Error + 'static,
+        D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+        X: Transport,
     {
         let mut buf = vec![0u8; 1024];
-        // For now, we give up on any send/recv/downcast/deserialize errors and
-        // rely on client to time out.
 
-        let res = unblock(move || {
-            let mut lcd = lcd.lock_arc_blocking();
-            lcd.set_backlight(backlight)
-        }).await;
-        let resp_res = if res.is_ok() {
-            SetBacklightResponse(Ok(()))
-        } else {
-            SetBacklightResponse(Err(RequestError {}))
+        let resp_res = match lcd {
+            Some(lcd) => {
+                // For now, we give up on any send/recv/downcast/deserialize
+                // errors and rely on client to time out.
+                let res = lcd::Lcd::set_backlight_async(lcd, backlight).await;
+                // The LCD driver only fails backlight control on the
+                // underlying HAL transaction.
+                SetBacklightResponse(res.map_err(|e| {
+                    let reason = match e {
+                        lcd::Error::SetBacklight(reason) => reason,
+                        _ => AbortReason::Other,
+                    };
+
+                    DeviceError::I2cBus(reason)
+                }))
+            }
+            None => SetBacklightResponse(Err(DeviceError::NotInitialized)),
         };
 
-        if let Ok(used) = postcard_rpc::headered::to_slice_keyed(seq_no, key, &resp_res, &mut buf) {
-            let _ = sock.send_to(used, addr).await;
+        if let Err(e) = resp_res.0 {
+            report_fault(&fault_send, e, Fault::Lcd);
+            crate::record_error(&errors, seq_no, key, DispatchError::Device(e));
         }
+
+        if resp_res.0.is_ok() {
+            if let Some(status_publish) = &status_publish {
+                let payload = match backlight {
+                    SetBacklight::On => b"on".to_vec(),
+                    SetBacklight::Off => b"off".to_vec(),
+                };
+                status_publish.publish("backlight", payload).await;
+            }
+        }
+
+        let _ = transport.send_keyed(seq_no, key, &resp_res, &mut buf).await;
     }
 
-    pub async fn echo<'a>(
+    pub async fn send_msg<'a, I2C, E, D, X>(
         _ex: Rc<LocalExecutor<'_>>,
         seq_no: u32,
         key: Key,
-        (sock, addr): (UdpSocket, SocketAddr),
+        transport: X,
+        lcd: Option<Arc<Mutex<lcd::Lcd<I2C, D>>>>,
+        SendMsg(text): SendMsg,
+    ) where
+        I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+        E: Send + std::error::Error + 'static,
+        D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+        X: Transport,
+    {
+        let mut buf = vec![0u8; 1024];
+
+        let resp_res = match lcd {
+            // `write_msg` only ever fails on a non-ASCII message or the
+            // underlying HAL transaction; neither is worth breaking out
+            // into `DeviceError`'s variants the way other endpoints do, so
+            // this one just reports the generic `RequestError` the wire
+            // type already carries.
+            Some(lcd) => SendMsgResponse(
+                lcd::Lcd::write_msg_async(lcd, text)
+                    .await
+                    .map_err(|_| RequestError {}),
+            ),
+            None => SendMsgResponse(Err(RequestError {})),
+        };
+
+        let _ = transport.send_keyed(seq_no, key, &resp_res, &mut buf).await;
+    }
+
+    pub async fn echo<'a, X>(
+        _ex: Rc<LocalExecutor<'_>>,
+        seq_no: u32,
+        key: Key,
+        transport: X,
         msg: String,
-    ) {
+    ) where
+        X: Transport,
+    {
         let resp = EchoResponse(msg.to_uppercase());
         let mut buf = vec![0u8; 1024];
 
-        if let Ok(used) = postcard_rpc::headered::to_slice_keyed(seq_no, key, &resp, &mut buf) {
-            let _ = sock.send_to(used, addr).await;
+        let _ = transport.send_keyed(seq_no, key, &resp, &mut buf).await;
+    }
+
+    /// Looks up the most recent failure recorded for `(seq_no, key)` in
+    /// [`crate::ErrorLog`], for a client that only got an opaque
+    /// `RequestError {}`/no reply at all to ask what actually happened.
+    pub async fn error_query<X>(
+        _ex: Rc<LocalExecutor<'_>>,
+        reply_seq_no: u32,
+        reply_key: Key,
+        transport: X,
+        errors: crate::ErrorLog,
+        ErrorQuery { seq_no, key }: ErrorQuery,
+    ) where
+        X: Transport,
+    {
+        let mut buf = vec![0u8; 1024];
+
+        let found = errors
+            .borrow()
+            .iter()
+            .rev()
+            .find(|((s, k), _)| *s == seq_no && *k == key)
+            .map(|(_, err)| err.clone());
+
+        let resp = LastErrorResponse(found);
+        let _ = transport
+            .send_keyed(reply_seq_no, reply_key, &resp, &mut buf)
+            .await;
+    }
+
+    pub async fn list_devices<X>(
+        _ex: Rc<LocalExecutor<'_>>,
+        seq_no: u32,
+        key: Key,
+        transport: X,
+        device_config: Option<Rc<RefCell<crate::config::DeviceConfig>>>,
+        ListDevices {}: ListDevices,
+    ) where
+        X: Transport,
+    {
+        let mut buf = vec![0u8; 1024];
+
+        let devices = device_config
+            .map(|cfg| cfg.borrow().read().to_vec())
+            .unwrap_or_default();
+
+        let resp = ListDevicesResponse(devices);
+        let _ = transport.send_keyed(seq_no, key, &resp, &mut buf).await;
+    }
+
+    /// Brings a device configured at runtime online: runs it through the
+    /// same [`crate::init_device`] startup uses, then persists it to
+    /// `device_config`, so [`set_led`]/[`notify`]/etc.
+    /// pick it up on the very next request regardless of which transport
+    /// they came in on.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn add_device<I2C, E, D, X>(
+        ex: Rc<LocalExecutor<'_>>,
+        seq_no: u32,
+        key: Key,
+        transport: X,
+        device_config: Option<Rc<RefCell<crate::config::DeviceConfig>>>,
+        errors: crate::ErrorLog,
+        bus: Option<Rc<dyn Fn() -> I2C>>,
+        bg_slot: Option<wb_notifier_driver::Slot<bargraph::Bargraph<I2C>>>,
+        lcd_slot: Option<wb_notifier_driver::Slot<lcd::Lcd<I2C, D>>>,
+        blink_send: crate::ChanSlot<BlinkInfo>,
+        marquee_send: crate::ChanSlot<background::MarqueeInfo>,
+        AddDevice(device): AddDevice,
+    ) where
+        I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+        E: Send + std::error::Error + 'static,
+        D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+        X: Transport,
+    {
+        let mut buf = vec![0u8; 1024];
+
+        let result = (|| -> Result<(), ConfigError> {
+            let Some(device_config) = &device_config else {
+                return Err(ConfigError::NotFound);
+            };
+            if device_config.borrow().contains(&device.name) {
+                return Err(ConfigError::Duplicate);
+            }
+
+            let (Some(bus), Some(bg_slot), Some(lcd_slot)) = (&bus, &bg_slot, &lcd_slot) else {
+                return Err(ConfigError::Init(DeviceError::NotInitialized));
+            };
+
+            // Each driver has exactly one shared slot, so a second device
+            // of the same `Driver` would silently overwrite the first
+            // one's slot out from under its still-running `blink`/
+            // `marquee` task rather than actually coexisting with it.
+            let busy = match device.driver {
+                Driver::Bargraph => bg_slot.borrow().is_some(),
+                Driver::Hd44780 => lcd_slot.borrow().is_some(),
+            };
+            if busy {
+                return Err(ConfigError::DriverBusy);
+            }
+
+            crate::init_device(&ex, bus, &device, bg_slot, lcd_slot, &blink_send, &marquee_send)
+                .map_err(|e| ConfigError::Init(DeviceError::I2cBus(e.reason())))?;
+
+            // Persisting the config isn't an I2C failure at all, but
+            // `ConfigError::Init` is the only variant that can carry a
+            // `DeviceError`; `Other` is the closest honest fit.
+            device_config
+                .borrow_mut()
+                .write(device.clone())
+                .map_err(|_| ConfigError::Init(DeviceError::I2cBus(AbortReason::Other)))
+        })();
+
+        if let Err(e) = &result {
+            crate::record_error(&errors, seq_no, key, DispatchError::Config(e.clone()));
+        }
+
+        let resp = AddDeviceResponse(result);
+        let _ = transport.send_keyed(seq_no, key, &resp, &mut buf).await;
+    }
+
+    /// Takes a device offline and out of `device_config`: clears whichever
+    /// slot it occupied so every live `Dispatch` table starts reporting
+    /// `NotInitialized` for it, and clears its `ChanSlot` so the background
+    /// `blink`/`marquee` task backing it sees its channel close and exits
+    /// on its next iteration. Clearing `bg_slot`/`lcd_slot` alone isn't
+    /// enough for that: the task holds its own clone of the `Arc<Mutex<_>>`
+    /// (see [`crate::init_device`]), so it would otherwise keep running
+    /// forever against the removed device until a later [`add_device`] of
+    /// the same driver overwrote the slot out from under it. Safe to clear
+    /// by driver alone: `add_device` refuses to bring up a second device of
+    /// the same `Driver` while one is already active, so at most one device
+    /// per driver is ever configured, and `removed` can only be the one
+    /// currently occupying that slot.
+    pub async fn remove_device<I2C, D, X>(
+        _ex: Rc<LocalExecutor<'_>>,
+        seq_no: u32,
+        key: Key,
+        transport: X,
+        device_config: Option<Rc<RefCell<crate::config::DeviceConfig>>>,
+        errors: crate::ErrorLog,
+        bg_slot: Option<wb_notifier_driver::Slot<bargraph::Bargraph<I2C>>>,
+        lcd_slot: Option<wb_notifier_driver::Slot<lcd::Lcd<I2C, D>>>,
+        blink_send: crate::ChanSlot<BlinkInfo>,
+        marquee_send: crate::ChanSlot<background::MarqueeInfo>,
+        RemoveDevice { name }: RemoveDevice,
+    ) where
+        X: Transport,
+    {
+        let mut buf = vec![0u8; 1024];
+
+        let result = (|| -> Result<(), ConfigError> {
+            let Some(device_config) = &device_config else {
+                return Err(ConfigError::NotFound);
+            };
+
+            let removed = device_config
+                .borrow_mut()
+                .remove(&name)
+                .map_err(|_| ConfigError::NotFound)?
+                .ok_or(ConfigError::NotFound)?;
+
+            match removed.driver {
+                Driver::Bargraph => {
+                    if let Some(slot) = &bg_slot {
+                        slot.borrow_mut().take();
+                    }
+                    blink_send.borrow_mut().take();
+                }
+                Driver::Hd44780 => {
+                    if let Some(slot) = &lcd_slot {
+                        slot.borrow_mut().take();
+                    }
+                    marquee_send.borrow_mut().take();
+                }
+            }
+
+            Ok(())
+        })();
+
+        if let Err(e) = &result {
+            crate::record_error(&errors, seq_no, key, DispatchError::Config(e.clone()));
+        }
+
+        let resp = RemoveDeviceResponse(result);
+        let _ = transport.send_keyed(seq_no, key, &resp, &mut buf).await;
+    }
+
+    /// Queues a [`ScheduleNotify`] onto the process-wide
+    /// [`background::schedule`] worker, which applies its `notify`
+    /// directly (rather than this handler doing so) once `schedule`
+    /// elapses. `id` is minted by the caller, since the heap the worker
+    /// tracks deadlines in is keyed by id rather than by LED number the
+    /// way [`BlinkInfo`] is: several schedules can target the same LED.
+    pub async fn schedule_notify<X>(
+        _ex: Rc<LocalExecutor<'_>>,
+        seq_no: u32,
+        key: Key,
+        transport: X,
+        errors: crate::ErrorLog,
+        clock: crate::sntp::Clock,
+        schedule_send: Option<Sender<background::ScheduleInfo>>,
+        id: u64,
+        ScheduleNotify { notify, schedule }: ScheduleNotify,
+    ) where
+        X: Transport,
+    {
+        let mut buf = vec![0u8; 1024];
+
+        let result = (|| -> Result<(), ScheduleError> {
+            let (at, every) = match schedule {
+                Schedule::At { epoch_secs } => {
+                    let now_unix = clock.now_unix().ok_or(ScheduleError::ClockNotSynced)?;
+                    let delay = epoch_secs.saturating_sub(now_unix);
+                    (Instant::now() + Duration::from_secs(delay), None)
+                }
+                Schedule::Every { secs } => {
+                    let period = Duration::from_secs(secs.into());
+                    (Instant::now() + period, Some(period))
+                }
+            };
+
+            schedule_send
+                .as_ref()
+                .ok_or(ScheduleError::Full)?
+                .try_send(background::ScheduleInfo::Add {
+                    id,
+                    at,
+                    every,
+                    notify,
+                })
+                .map_err(|_| ScheduleError::Full)
+        })();
+
+        if let Err(e) = &result {
+            crate::record_error(&errors, seq_no, key, DispatchError::Schedule(*e));
         }
+
+        let resp = ScheduleNotifyResponse(result);
+        let _ = transport.send_keyed(seq_no, key, &resp, &mut buf).await;
     }
 }
 
 pub(super) mod background {
-    use std::time::Duration;
+    use std::cmp::Reverse;
+    use std::collections::{BinaryHeap, HashMap};
+    use std::time::{Duration, Instant};
 
     use super::*;
-    use async_channel::{Receiver, TryRecvError};
-    use async_executor::Task;
+    use async_channel::Receiver;
     use async_io::Timer;
-    use futures_lite::future;
+    use embedded_hal::blocking::delay::{DelayMs, DelayUs};
     use futures_lite::{Future, FutureExt};
+    use linux_embedded_hal::{Delay, I2cdev};
 
     enum FinishedFirst<U, T> {
         Us(U),
@@ -246,131 +748,517 @@ pub(super) mod background {
         us.or(them).await
     }
 
-    enum BlinkState {
-        Init,
-        Off,
+    #[derive(Clone, Copy, Debug)]
+    pub enum BlinkInfo {
+        LedSet { num: u8 },
+        LedClear { num: u8 },
+        ClearAll,
+        /// Change how long an LED dwells at `Fast`/`Medium` before
+        /// escalating; see [`BlinkThresholds`]. Takes effect for
+        /// escalations scheduled from this point on, not retroactively.
+        SetThresholds(BlinkThresholds),
+    }
+
+    /// How long an LED dwells at [`Urgency::Fast`]/[`Urgency::Medium`]
+    /// before escalating to the next stage; see `SetBlinkThresholds` on
+    /// the wire. Runtime-configurable via [`BlinkInfo::SetThresholds`]
+    /// instead of the fixed 60s/300s [`Default`] below.
+    #[derive(Clone, Copy, Debug)]
+    pub struct BlinkThresholds {
+        fast_to_medium: Duration,
+        medium_to_slow: Duration,
+    }
+
+    impl Default for BlinkThresholds {
+        fn default() -> Self {
+            Self {
+                fast_to_medium: Duration::from_secs(60),
+                medium_to_slow: Duration::from_secs(300),
+            }
+        }
+    }
+
+    impl From<SetBlinkThresholds> for BlinkThresholds {
+        fn from(
+            SetBlinkThresholds {
+                fast_to_medium_secs,
+                medium_to_slow_secs,
+            }: SetBlinkThresholds,
+        ) -> Self {
+            Self {
+                fast_to_medium: Duration::from_secs(fast_to_medium_secs.into()),
+                medium_to_slow: Duration::from_secs(medium_to_slow_secs.into()),
+            }
+        }
+    }
+
+    /// How urgently a single LED's unacked notification has escalated.
+    /// The *display's* effective blink rate is the max of this across
+    /// every currently-tracked LED, since the HT16K33 only exposes one
+    /// global blink register.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    enum Urgency {
         Fast,
-        Med,
+        Medium,
         Slow,
     }
 
-    #[derive(Clone, Copy, Debug)]
-    pub enum BlinkInfo {
-        LedSet,
-        LedClear,
+    impl Urgency {
+        fn display(self) -> bargraph::Display {
+            match self {
+                Urgency::Fast => bargraph::Display::TWO_HZ,
+                Urgency::Medium => bargraph::Display::ONE_HZ,
+                Urgency::Slow => bargraph::Display::HALF_HZ,
+            }
+        }
+
+        /// How long this urgency dwells before escalating, per
+        /// `thresholds`. `Slow` has nowhere further to escalate to, so its
+        /// value is never actually consulted by [`blink`].
+        fn dwell(self, thresholds: BlinkThresholds) -> Duration {
+            match self {
+                Urgency::Fast => thresholds.fast_to_medium,
+                Urgency::Medium => thresholds.medium_to_slow,
+                Urgency::Slow => Duration::MAX,
+            }
+        }
+
+        /// `None` once at `Slow`; there's nowhere further to escalate, so
+        /// the LED just stays at `Slow` until it's acked.
+        fn next(self) -> Option<Urgency> {
+            match self {
+                Urgency::Fast => Some(Urgency::Medium),
+                Urgency::Medium => Some(Urgency::Slow),
+                Urgency::Slow => None,
+            }
+        }
+    }
+
+    struct LedEscalation {
+        urgency: Urgency,
+        /// When this entry's `urgency` will next advance. Used to
+        /// recognize and discard stale heap entries left behind by an
+        /// earlier escalation of the same LED.
+        deadline: Instant,
     }
 
+    /// Job-queue-style worker: `req_recv` carries per-LED set/clear
+    /// events, and a min-heap of `(deadline, num)` drives escalation for
+    /// whichever LEDs are still active. Firing a deadline advances only
+    /// that LED's own stage and re-pushes its next one; the display's
+    /// blink register is recomputed as the max urgency across the active
+    /// set every time it changes.
     pub async fn blink<'a, I2C, E>(
-        ex: Rc<LocalExecutor<'_>>,
+        _ex: Rc<LocalExecutor<'_>>,
         bg: Arc<Mutex<bargraph::Bargraph<I2C>>>,
-        // For now, dispatch to blink task from server without having a channel
-        // to send a response.
         req_recv: Receiver<BlinkInfo>,
     ) where
         I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
-        E: Send + 'static,
+        E: Send + std::error::Error + 'static,
     {
-        let mut state = BlinkState::Init;
-        let (wait_done_send, wait_done_recv) = bounded(1);
-        // let (driver_resp_send, driver_resp_recv) = bounded(1);
+        let mut active: HashMap<u8, LedEscalation> = HashMap::new();
+        let mut heap: BinaryHeap<Reverse<(Instant, u8)>> = BinaryHeap::new();
+        let mut current: Option<Urgency> = None;
+        let mut thresholds = BlinkThresholds::default();
 
         loop {
-            let curr_task: Task<()>;
-            match state {
-                BlinkState::Init => {
-                    curr_task = ex.spawn(future::pending());
+            let timer = match heap.peek() {
+                Some(Reverse((at, _))) => {
+                    Timer::after(at.saturating_duration_since(Instant::now()))
                 }
-                BlinkState::Off => {
-                    let bg = bg.clone();
-                    unblock(move || {
-                        let _ = bg.lock_arc_blocking().set_display(bargraph::Display::ON);
-                    })
-                    .await;
-                    curr_task = ex.spawn(future::pending());
+                None => Timer::never(),
+            };
+
+            match select(req_recv.recv(), timer).await {
+                FinishedFirst::Us(Ok(BlinkInfo::LedSet { num })) => {
+                    let deadline = Instant::now() + Urgency::Fast.dwell(thresholds);
+                    active.insert(
+                        num,
+                        LedEscalation {
+                            urgency: Urgency::Fast,
+                            deadline,
+                        },
+                    );
+                    heap.push(Reverse((deadline, num)));
+                    apply_rate(&bg, &mut current, &active).await;
                 }
-                BlinkState::Fast => {
-                    let bg = bg.clone();
-                    unblock(move || {
-                        let _ = bg
-                            .lock_arc_blocking()
-                            .set_display(bargraph::Display::TWO_HZ);
-                    })
-                    .await;
-                    curr_task = ex.spawn(wait_then_send_done(
-                        Duration::from_secs(60),
-                        wait_done_send.clone(),
-                    ));
+                FinishedFirst::Us(Ok(BlinkInfo::LedClear { num })) => {
+                    active.remove(&num);
+                    apply_rate(&bg, &mut current, &active).await;
                 }
-                BlinkState::Med => {
-                    let bg = bg.clone();
-                    unblock(move || {
-                        let _ = bg
-                            .lock_arc_blocking()
-                            .set_display(bargraph::Display::ONE_HZ);
-                    })
-                    .await;
-                    curr_task = ex.spawn(wait_then_send_done(
-                        Duration::from_secs(300),
-                        wait_done_send.clone(),
-                    ));
+                FinishedFirst::Us(Ok(BlinkInfo::ClearAll)) => {
+                    active.clear();
+                    heap.clear();
+                    apply_rate(&bg, &mut current, &active).await;
                 }
-                BlinkState::Slow => {
-                    let bg = bg.clone();
-                    unblock(move || {
-                        let _ = bg
-                            .lock_arc_blocking()
-                            .set_display(bargraph::Display::HALF_HZ);
-                    })
-                    .await;
-                    curr_task = ex.spawn(wait_then_send_done(
-                        Duration::from_secs(900),
-                        wait_done_send.clone(),
-                    ));
+                FinishedFirst::Us(Ok(BlinkInfo::SetThresholds(new_thresholds))) => {
+                    thresholds = new_thresholds;
                 }
-            }
+                FinishedFirst::Us(Err(_)) => break,
+                FinishedFirst::Them(_) => {
+                    let now = Instant::now();
 
-            match select(req_recv.recv(), wait_done_recv.clone().recv()).await {
-                FinishedFirst::Them(_) => match state {
-                    BlinkState::Init | BlinkState::Slow => {
-                        state = BlinkState::Off;
-                    }
-                    BlinkState::Off => {
-                        state = BlinkState::Fast;
+                    while let Some(&Reverse((at, num))) = heap.peek() {
+                        if at > now {
+                            break;
+                        }
+                        heap.pop();
+
+                        // A stale entry from an urgency this LED has
+                        // already moved past (or a cleared LED); skip it.
+                        let Some(esc) = active.get_mut(&num) else {
+                            continue;
+                        };
+                        if esc.deadline != at {
+                            continue;
+                        }
+
+                        if let Some(next) = esc.urgency.next() {
+                            esc.urgency = next;
+                            esc.deadline = now + next.dwell(thresholds);
+                            heap.push(Reverse((esc.deadline, num)));
+                        }
                     }
-                    BlinkState::Fast => {
-                        state = BlinkState::Med;
+
+                    apply_rate(&bg, &mut current, &active).await;
+                }
+            }
+        }
+    }
+
+    /// Re-derives the display's blink rate as the max urgency across
+    /// `active` and writes it if it differs from `current`.
+    async fn apply_rate<I2C, E>(
+        bg: &Arc<Mutex<bargraph::Bargraph<I2C>>>,
+        current: &mut Option<Urgency>,
+        active: &HashMap<u8, LedEscalation>,
+    ) where
+        I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+        E: Send + std::error::Error + 'static,
+    {
+        let target = active.values().map(|e| e.urgency).max();
+        if *current == target {
+            return;
+        }
+
+        let disp = target.map_or(bargraph::Display::ON, Urgency::display);
+        let _ = bargraph::Bargraph::set_display_async(bg.clone(), disp).await;
+
+        *current = target;
+    }
+
+    /// A `notify`/`ack` routed to the LCD, keyed by LED `num` the same way
+    /// [`BlinkInfo`] is.
+    #[derive(Clone, Debug)]
+    pub enum MarqueeInfo {
+        /// Show `text` on the row `num` maps to, scrolling it if it's
+        /// wider than [`lcd::LCD_COLS`].
+        Show { num: u8, text: String },
+        /// Blank whichever row `num` maps to.
+        Clear { num: u8 },
+        /// Blank every row.
+        ClearAll,
+    }
+
+    /// Owns one scroll task per occupied LCD row, keyed by `num %
+    /// lcd::LCD_ROWS` (several LEDs can share a row on a display with
+    /// fewer rows than the bargraph has LEDs). A fresh
+    /// [`MarqueeInfo::Show`] for a row drops whatever was scrolling there
+    /// before spawning its replacement, the same restart-on-conflict
+    /// behavior [`blink`] gets from re-pushing the heap.
+    ///
+    /// This, plus [`lcd::Lcd::write_line_async`]'s `unblock` offload, is
+    /// the actual non-blocking LCD renderer; an earlier attempt at one
+    /// driven by a cooperative `step()`/`LineFsm` inside `driver/src/lcd.rs`
+    /// never had a caller and was reverted.
+    pub async fn marquee<I2C, E, D>(
+        ex: Rc<LocalExecutor<'_>>,
+        lcd: Arc<Mutex<lcd::Lcd<I2C, D>>>,
+        req_recv: Receiver<MarqueeInfo>,
+    ) where
+        I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+        E: Send + std::error::Error + 'static,
+        D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+    {
+        let mut rows: HashMap<u8, async_executor::Task<()>> = HashMap::new();
+
+        while let Ok(info) = req_recv.recv().await {
+            match info {
+                MarqueeInfo::Show { num, text } => {
+                    let row = num % lcd::LCD_ROWS;
+                    rows.insert(row, ex.spawn(scroll(lcd.clone(), row, text)));
+                }
+                MarqueeInfo::Clear { num } => {
+                    let row = num % lcd::LCD_ROWS;
+                    rows.remove(&row);
+                    blank_row(&lcd, row).await;
+                }
+                MarqueeInfo::ClearAll => {
+                    rows.clear();
+                    for row in 0..lcd::LCD_ROWS {
+                        blank_row(&lcd, row).await;
                     }
-                    BlinkState::Med => {
-                        state = BlinkState::Slow;
+                }
+            }
+        }
+    }
+
+    async fn blank_row<I2C, E, D>(lcd: &Arc<Mutex<lcd::Lcd<I2C, D>>>, row: u8)
+    where
+        I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+        E: Send + std::error::Error + 'static,
+        D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+    {
+        let _ = lcd::Lcd::write_line_async(lcd.clone(), row, String::new()).await;
+    }
+
+    /// How long a scrolling message dwells at each column offset.
+    const MARQUEE_TICK: Duration = Duration::from_millis(300);
+
+    /// Writes `text` to `row` once if it fits in [`lcd::LCD_COLS`];
+    /// otherwise shifts a windowed view of it across `row` one column
+    /// every [`MARQUEE_TICK`], with a blank gap column between the end of
+    /// the message and its next pass. Runs until the [`marquee`] task
+    /// drops this row's [`async_executor::Task`], cancelling it mid-wait.
+    async fn scroll<I2C, E, D>(lcd: Arc<Mutex<lcd::Lcd<I2C, D>>>, row: u8, text: String)
+    where
+        I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+        E: Send + std::error::Error + 'static,
+        D: DelayMs<u8> + DelayUs<u16> + Send + 'static,
+    {
+        let cols = lcd::LCD_COLS as usize;
+        let chars: Vec<char> = text.chars().collect();
+
+        if chars.len() <= cols {
+            let _ = lcd::Lcd::write_line_async(lcd.clone(), row, text).await;
+            return;
+        }
+
+        // One blank gap column separates the end of the message from its
+        // next pass across the window.
+        let period = chars.len() + 1;
+        let mut offset = 0;
+
+        loop {
+            let window: String = (0..cols)
+                .map(|i| *chars.get((offset + i) % period).unwrap_or(&' '))
+                .collect();
+
+            let _ = lcd::Lcd::write_line_async(lcd.clone(), row, window).await;
+
+            offset = (offset + 1) % period;
+            Timer::after(MARQUEE_TICK).await;
+        }
+    }
+
+    /// A `schedule_notify` request queued onto [`schedule`], keyed by an
+    /// id the handler mints fresh per request. Unlike [`BlinkInfo`]/
+    /// [`MarqueeInfo`] there's no natural small key to reuse (an LED
+    /// number): several schedules can target the same LED.
+    #[derive(Clone, Debug)]
+    pub enum ScheduleInfo {
+        Add {
+            id: u64,
+            at: Instant,
+            every: Option<Duration>,
+            notify: Notify,
+        },
+    }
+
+    /// Single process-wide worker backing `schedule_notify`: a min-heap of
+    /// `(deadline, id)` drives firing, the same design [`blink`] uses for
+    /// per-LED escalation. Firing applies `notify` by calling
+    /// [`handlers::notify`] directly through a [`crate::transport::NullTransport`]
+    /// rather than duplicating its apply/blink/marquee/peer-mirror/
+    /// status-publish logic. `bg` is the live slot (not a snapshot), so a
+    /// device brought up or removed after a schedule was set is picked up
+    /// fresh on every fire.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn schedule<I2C, E>(
+        ex: Rc<LocalExecutor<'_>>,
+        bg: wb_notifier_driver::Slot<bargraph::Bargraph<I2C>>,
+        fault_send: Option<Sender<Fault>>,
+        errors: crate::ErrorLog,
+        blink_send: crate::ChanSlot<BlinkInfo>,
+        marquee_send: crate::ChanSlot<MarqueeInfo>,
+        peer_fanout: Option<crate::PeerFanout>,
+        status_publish: Option<crate::StatusPublish>,
+        req_recv: Receiver<ScheduleInfo>,
+    ) where
+        I2C: Send + Write<Error = E> + WriteRead<Error = E> + 'static,
+        E: Send + std::error::Error + 'static,
+    {
+        let key = Key::for_path::<Notify>(NOTIFY_PATH);
+        let mut due: HashMap<u64, (Option<Duration>, Notify)> = HashMap::new();
+        let mut heap: BinaryHeap<Reverse<(Instant, u64)>> = BinaryHeap::new();
+
+        loop {
+            let timer = match heap.peek() {
+                Some(Reverse((at, _))) => {
+                    Timer::after(at.saturating_duration_since(Instant::now()))
+                }
+                None => Timer::never(),
+            };
+
+            match select(req_recv.recv(), timer).await {
+                FinishedFirst::Us(Ok(ScheduleInfo::Add { id, at, every, notify })) => {
+                    due.insert(id, (every, notify));
+                    heap.push(Reverse((at, id)));
+                }
+                FinishedFirst::Us(Err(_)) => break,
+                FinishedFirst::Them(_) => {
+                    let now = Instant::now();
+
+                    while let Some(&Reverse((at, id))) = heap.peek() {
+                        if at > now {
+                            break;
+                        }
+                        heap.pop();
+
+                        // A stale entry left behind by a schedule that's
+                        // since fired for good (`every: None`); skip it.
+                        let Some((every, notify)) = due.get(&id).cloned() else {
+                            continue;
+                        };
+
+                        handlers::notify(
+                            ex.clone(),
+                            0,
+                            key,
+                            crate::transport::NullTransport,
+                            fault_send.clone(),
+                            errors.clone(),
+                            blink_send.borrow().clone(),
+                            marquee_send.borrow().clone(),
+                            peer_fanout.clone(),
+                            status_publish.clone(),
+                            false,
+                            bg.borrow().clone(),
+                            notify,
+                        )
+                        .await;
+
+                        match every {
+                            Some(period) => {
+                                heap.push(Reverse((now + period, id)));
+                            }
+                            None => {
+                                due.remove(&id);
+                            }
+                        }
                     }
-                },
-                FinishedFirst::Us(Ok(led)) => {
-                    curr_task.cancel().await;
-                    // Drain the channel to ensure it's empty for the next time
-                    // we run the task.
-                    if let Err(TryRecvError::Closed) = wait_done_recv.try_recv() {
-                        break;
+                }
+            }
+        }
+    }
+
+    /// A device whose last HAL transaction failed; `relaxed` mode reports
+    /// these to [`reconnect`] instead of letting the error kill the server.
+    #[derive(Clone, Copy, Debug)]
+    pub enum Fault {
+        Bargraph,
+        Lcd,
+    }
+
+    const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+    const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+    /// Waits on `fault_recv` for a bus fault, then retries reopening
+    /// `path` and `reinit`-ing whichever of `bg`/`lcd` are configured on a
+    /// doubling, capped backoff until both come back online. Any faults
+    /// reported while a retry is already underway are coalesced into that
+    /// same attempt.
+    pub async fn reconnect(
+        path: String,
+        bg: Option<Arc<Mutex<bargraph::Bargraph<I2cdev>>>>,
+        lcd: Option<Arc<Mutex<lcd::Lcd<I2cdev, Delay>>>>,
+        fault_recv: Receiver<Fault>,
+    ) {
+        while fault_recv.recv().await.is_ok() {
+            let mut backoff = INITIAL_BACKOFF;
+
+            loop {
+                Timer::after(backoff).await;
+
+                let path = path.clone();
+                let bg = bg.clone();
+                let lcd = lcd.clone();
+
+                let reconnected = unblock(move || {
+                    let i2c = I2cdev::new(&path).map_err(|_| ())?;
+                    let bus: &'static _ = shared_bus::new_std!(I2cdev = i2c).ok_or(())?;
+
+                    if let Some(bg) = &bg {
+                        let mut bg = bg.lock_arc_blocking();
+                        bg.reinit(bus.acquire_i2c()).map_err(|_| ())?;
+                        // Re-apply the startup brightness; `reinit` only
+                        // re-runs `initialize`, which doesn't know the
+                        // dimming level `main_loop` picked.
+                        bg.set_dimming(bargraph::Dimming::BRIGHTNESS_3_16)
+                            .map_err(|_| ())?;
                     }
 
-                    match led {
-                        BlinkInfo::LedSet => state = BlinkState::Fast,
-                        BlinkInfo::LedClear => state = BlinkState::Off,
+                    if let Some(lcd) = &lcd {
+                        lcd.lock_arc_blocking()
+                            .reinit(bus.acquire_i2c())
+                            .map_err(|_| ())?;
                     }
-                }
-                FinishedFirst::Us(Err(_)) => {
-                    curr_task.cancel().await;
+
+                    Ok::<(), ()>(())
+                })
+                .await;
+
+                if reconnected.is_ok() {
                     break;
                 }
+
+                backoff = (backoff * 2).min(MAX_BACKOFF);
             }
+
+            // Coalesce any faults that piled up while we were busy
+            // reconnecting instead of immediately retrying again.
+            while fault_recv.try_recv().is_ok() {}
         }
     }
 
-    async fn wait_then_send_done(amt: Duration, done: Sender<()>) {
-        Timer::after(amt).await;
-        // This unwrap should never fire, because only one of these tasks
-        // active at any given time.
-        // If we cancel the task, we check the output and drain the channel
-        // to ensure it's empty for the next time we run the task.
-        done.send(()).await.unwrap();
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Exercises the escalation ladder [`blink`] drives: `Fast` ->
+        /// `Medium` -> `Slow`, with nowhere further to go once at `Slow`.
+        #[test]
+        fn urgency_escalates_fast_to_slow_then_stops() {
+            assert_eq!(Urgency::Fast.next(), Some(Urgency::Medium));
+            assert_eq!(Urgency::Medium.next(), Some(Urgency::Slow));
+            assert_eq!(Urgency::Slow.next(), None);
+        }
+
+        #[test]
+        fn urgency_dwell_matches_configured_thresholds() {
+            let thresholds = BlinkThresholds {
+                fast_to_medium: Duration::from_secs(5),
+                medium_to_slow: Duration::from_secs(30),
+            };
+
+            assert_eq!(Urgency::Fast.dwell(thresholds), Duration::from_secs(5));
+            assert_eq!(Urgency::Medium.dwell(thresholds), Duration::from_secs(30));
+            // `Slow` never escalates further, so its dwell is never
+            // actually consulted by `blink`; `MAX` just marks that there
+            // is no finite next stage.
+            assert_eq!(Urgency::Slow.dwell(thresholds), Duration::MAX);
+        }
+
+        #[test]
+        fn blink_thresholds_from_wire_type_converts_seconds() {
+            let thresholds: BlinkThresholds = SetBlinkThresholds {
+                fast_to_medium_secs: 60,
+                medium_to_slow_secs: 300,
+            }
+            .into();
+
+            assert_eq!(thresholds.fast_to_medium, Duration::from_secs(60));
+            assert_eq!(thresholds.medium_to_slow, Duration::from_secs(300));
+        }
     }
 }
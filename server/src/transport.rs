@@ -0,0 +1,124 @@
+//! Abstracts "receive a keyed postcard-rpc frame" / "send a keyed response
+//! frame" so the handlers in [`crate::tasks::handlers`] don't need to know
+//! whether they're talking UDP or a COBS-framed serial link. [`UdpTransport`]
+//! is always available; [`crate::serial::SerialTransport`] is the
+//! `feature = "serial"` alternative.
+
+use std::error;
+use std::fmt;
+use std::io;
+
+use async_net::{SocketAddr, UdpSocket};
+use postcard::experimental::schema::Schema;
+use postcard_rpc::Key;
+use serde::Serialize;
+
+/// A reply destination bound to one already-dispatched request. Handlers
+/// receive `impl Transport` in place of the `(UdpSocket, SocketAddr)` tuple
+/// they used to take directly.
+pub trait Transport: Clone {
+    type Error: error::Error + Send + Sync + 'static;
+
+    /// Frame `payload` as a keyed postcard-rpc response (using `buf` as
+    /// scratch space) and send it back to whoever made the request this
+    /// transport was bound to.
+    async fn send_keyed<T>(
+        &self,
+        seq_no: u32,
+        key: Key,
+        payload: &T,
+        buf: &mut [u8],
+    ) -> Result<(), Self::Error>
+    where
+        T: Schema + Serialize;
+
+    /// The address of whoever sent the request this transport was bound
+    /// to, if this transport kind has one. `UdpTransport` has a peer
+    /// address; transports without one (e.g. serial) default to `None`.
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub enum UdpTransportError {
+    Io(io::Error),
+    Encode(postcard::Error),
+}
+
+impl fmt::Display for UdpTransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UdpTransportError::Io(_) => write!(f, "io error sending UDP response"),
+            UdpTransportError::Encode(_) => write!(f, "could not frame postcard-rpc response"),
+        }
+    }
+}
+
+impl error::Error for UdpTransportError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            UdpTransportError::Io(e) => Some(e),
+            UdpTransportError::Encode(e) => Some(e),
+        }
+    }
+}
+
+/// A UDP socket plus the peer address a given request arrived from.
+#[derive(Clone)]
+pub struct UdpTransport {
+    pub sock: UdpSocket,
+    pub peer: SocketAddr,
+}
+
+impl Transport for UdpTransport {
+    type Error = UdpTransportError;
+
+    async fn send_keyed<T>(
+        &self,
+        seq_no: u32,
+        key: Key,
+        payload: &T,
+        buf: &mut [u8],
+    ) -> Result<(), Self::Error>
+    where
+        T: Schema + Serialize,
+    {
+        let used = postcard_rpc::headered::to_slice_keyed(seq_no, key, payload, buf)
+            .map_err(UdpTransportError::Encode)?;
+        self.sock
+            .send_to(used, self.peer)
+            .await
+            .map_err(UdpTransportError::Io)?;
+
+        Ok(())
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        Some(self.peer)
+    }
+}
+
+/// Stands in for a real transport when a handler is invoked with no
+/// requester to reply to, e.g. a [`crate::tasks::background::schedule`]d
+/// notification firing on its own timer rather than in response to a
+/// live request. `send_keyed` just drops the reply on the floor.
+#[derive(Clone, Copy, Debug)]
+pub struct NullTransport;
+
+impl Transport for NullTransport {
+    type Error = std::convert::Infallible;
+
+    async fn send_keyed<T>(
+        &self,
+        _seq_no: u32,
+        _key: Key,
+        _payload: &T,
+        _buf: &mut [u8],
+    ) -> Result<(), Self::Error>
+    where
+        T: Schema + Serialize,
+    {
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 /// Inspired by: https://github.com/jasonpeacock/led-bargraph, tweaked for
 /// my purposes.
 
+use embedded_hal::blocking::delay::DelayMs;
 use embedded_hal::blocking::i2c::{Write, WriteRead};
 use std::error;
 use std::fmt;
@@ -9,8 +10,16 @@ use ht16k33::{
     Dimming, Display, DisplayData, LedLocation, Oscillator, COMMONS_SIZE, HT16K33, ROWS_SIZE,
 };
 
+/// Number of LEDs this bargraph exposes, i.e. valid `num`s are `0..=23`.
+const NUM_LEDS: u8 = 24;
+
 pub struct Bargraph<I2C> {
     drv: HT16K33<I2C>,
+    /// Mirrors what's currently been pushed to the hardware, one byte
+    /// (the `LedColor` discriminant) per LED. `ht16k33::HT16K33` doesn't
+    /// expose its own display buffer for readback, so [`Bargraph::get_state`]
+    /// serves this cache instead of the real one.
+    buffer: [u8; NUM_LEDS as usize],
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -52,6 +61,18 @@ where
 
 impl<E> error::Error for Error<E> where E: error::Error {}
 
+/// Converts a byte stored in [`Bargraph::buffer`] back into the
+/// [`LedColor`] it was cast from; anything [`Bargraph::set_led_no`]
+/// couldn't have actually written falls back to `Off`.
+fn color_from_byte(byte: u8) -> LedColor {
+    match byte {
+        b if b == LedColor::Green as u8 => LedColor::Green,
+        b if b == LedColor::Red as u8 => LedColor::Red,
+        b if b == LedColor::Yellow as u8 => LedColor::Yellow,
+        _ => LedColor::Off,
+    }
+}
+
 impl<I2C, E> Bargraph<I2C>
 where
     I2C: Write<Error = E> + WriteRead<Error = E>,
@@ -59,7 +80,10 @@ where
     pub fn new(i2c: I2C, addr: u8) -> Self {
         let drv = HT16K33::new(i2c, addr);
 
-        Bargraph { drv }
+        Bargraph {
+            drv,
+            buffer: [LedColor::Off as u8; NUM_LEDS as usize],
+        }
     }
 
     pub fn initialize(&mut self) -> Result<(), Error<E>> {
@@ -100,6 +124,58 @@ where
         }
 
         self.drv.write_display_buffer()?;
+        self.buffer[num as usize] = color as u8;
+
+        Ok(())
+    }
+
+    /// The display buffer [`Bargraph::set_led_no`] has most recently
+    /// pushed to the hardware, one byte per LED. Lets a caller inspect
+    /// what the panel should currently be showing, e.g. to restore it
+    /// after [`Bargraph::self_test`].
+    #[must_use]
+    pub fn get_state(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Exercises every LED in turn, cycles the dimming and blink-rate
+    /// levels, and restores the buffer [`Bargraph::get_state`] had
+    /// beforehand. Modeled on the "inspect state, then self-test before
+    /// trusting the device" pattern firmware updaters use (read back
+    /// state, run the test, restore it before marking the device
+    /// booted), so a freshly (re)connected panel can be validated without
+    /// clobbering whatever it was already showing.
+    pub fn self_test<D>(&mut self, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u16>,
+    {
+        let prior = self.buffer;
+
+        for num in 0..NUM_LEDS {
+            for color in [LedColor::Red, LedColor::Green, LedColor::Yellow, LedColor::Off] {
+                self.set_led_no(num, color)?;
+                delay.delay_ms(20);
+            }
+        }
+
+        for dim in [
+            Dimming::BRIGHTNESS_4_16,
+            Dimming::BRIGHTNESS_8_16,
+            Dimming::BRIGHTNESS_12_16,
+            Dimming::BRIGHTNESS_16_16,
+        ] {
+            self.set_dimming(dim)?;
+            delay.delay_ms(200);
+        }
+
+        for disp in [Display::TWO_HZ, Display::ONE_HZ, Display::HALF_HZ, Display::ON] {
+            self.set_display(disp)?;
+            delay.delay_ms(200);
+        }
+
+        for (num, byte) in prior.into_iter().enumerate() {
+            self.set_led_no(num as u8, color_from_byte(byte))?;
+        }
 
         Ok(())
     }
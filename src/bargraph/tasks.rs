@@ -46,6 +46,13 @@ pub enum BargraphCmd {
     StopBlink {
         resp: CmdResponse<Result<()>>,
     },
+    /// Re-open the I2C device and re-run initialization without tearing
+    /// down the event loop task, e.g. in response to SIGHUP.
+    Reinit {
+        device: String,
+        addr: u8,
+        resp: CmdResponse<Result<()>>,
+    },
 }
 
 pub struct BlinkTask {
@@ -67,119 +74,130 @@ pub async fn blink_task(
     send: sync::mpsc::Sender<BargraphCmd>,
     mut recv: sync::mpsc::Receiver<BlinkInfo>,
     err: sync::mpsc::Sender<Result<()>>,
-    shutdown: sync::watch::Receiver<ServerState>,
+    mut shutdown: sync::watch::Receiver<ServerState>,
 ) {
     loop {
-        if let Some(bi) = recv.recv().await {
-            if let BlinkInfo::LedSet(_) = bi {
-                blink_loop(&send, &mut recv, bi).await;
-            } else {
-                // LED was cleared when no pending timers... nothing to do.
-                continue;
+        tokio::select! {
+            r = recv.recv() => {
+                match r {
+                    Some(bi @ BlinkInfo::LedSet(_)) => {
+                        if !blink_loop(&send, &mut recv, &mut shutdown, bi).await {
+                            break;
+                        }
+                    }
+                    Some(BlinkInfo::LedClear(_)) => {
+                        // LED was cleared when no pending timers... nothing to do.
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+            _ = shutdown.changed() => {
+                if *shutdown.borrow() == ServerState::ShuttingDown {
+                    break;
+                }
             }
-        } else {
-            break;
         }
     }
 }
 
+/// Outcome of waiting out one escalation stage's timeout.
+enum StageResult {
+    /// The stage's timer elapsed; advance to the next escalation level.
+    Elapsed,
+    /// A new LED was set; restart escalation from the fastest blink rate.
+    Reset,
+    /// An LED was cleared, the channel closed, or shutdown was requested;
+    /// unwind and stop blinking.
+    Stop { shutdown: bool },
+}
+
 // TODO: Make aware of current LEDs lit and which ones aren't to figure out
 // actual time to stop blinking (use oneshots to send info about LED numbers?)
+//
+// Returns `false` once the task should exit because `shutdown` transitioned
+// to `ServerState::ShuttingDown`.
 pub async fn blink_loop(
     send: &sync::mpsc::Sender<BargraphCmd>,
     recv: &mut sync::mpsc::Receiver<BlinkInfo>,
+    shutdown: &mut sync::watch::Receiver<ServerState>,
     _bi_init: BlinkInfo,
-) {
+) -> bool {
     'blink_timer_reset: loop {
-        let (resp, resp_rx) = sync::oneshot::channel();
-        send.send(BargraphCmd::StartBlink { resp }).await;
-        resp_rx.await;
+        for (cmd_for_rate, amt) in [
+            (BargraphCmdKind::Fast, Duration::new(60, 0)),
+            (BargraphCmdKind::Medium, Duration::new(300, 0)),
+            (BargraphCmdKind::Slow, Duration::new(900, 0)),
+        ] {
+            let (resp, resp_rx) = sync::oneshot::channel();
+            let _ = send.send(cmd_for_rate.into_cmd(resp)).await;
+            let _ = resp_rx.await;
 
-        let sleep = time::sleep(Duration::new(60, 0));
-        tokio::pin!(sleep);
-
-        // Yikes! Refactor later...
-        loop {
-            tokio::select! {
-                // Recv can fail... bail completely from function if so.
-                r = recv.recv() => {
-                    if let Some(bi) = r {
-                        if let BlinkInfo::LedSet(_) = bi {
-                            continue 'blink_timer_reset;
-                        } else {
-                            // LED cleared... cancel.
-                            break 'blink_timer_reset;
-                        }
-                    } else {
-                        return;
-                    }
-                },
-                _ = &mut sleep => {
-                    break;
+            match wait_stage(recv, shutdown, amt).await {
+                StageResult::Elapsed => continue,
+                StageResult::Reset => continue 'blink_timer_reset,
+                StageResult::Stop { shutdown } => {
+                    stop_blink(send).await;
+                    return !shutdown;
                 }
             }
         }
 
-        let (resp, resp_rx) = sync::oneshot::channel();
-        send.send(BargraphCmd::MediumBlink { resp }).await;
-        resp_rx.await;
+        break;
+    }
 
-        let sleep = time::sleep(Duration::new(300, 0));
-        tokio::pin!(sleep);
+    stop_blink(send).await;
+    true
+}
 
-        loop {
-            tokio::select! {
-                // Recv can fail... bail completely from function if so.
-                r = recv.recv() => {
-                    if let Some(bi) = r {
-                        if let BlinkInfo::LedSet(_) = bi {
-                            continue 'blink_timer_reset;
-                        } else {
-                            // LED cleared... cancel.
-                            break 'blink_timer_reset;
-                        }
-                    } else {
-                        return;
-                    }
-                },
-                _ = &mut sleep => {
-                    break;
-                }
-            }
-        }
+#[derive(Clone, Copy)]
+enum BargraphCmdKind {
+    Fast,
+    Medium,
+    Slow,
+}
 
-        let (resp, resp_rx) = sync::oneshot::channel();
-        send.send(BargraphCmd::SlowBlink { resp }).await;
-        resp_rx.await;
+impl BargraphCmdKind {
+    fn into_cmd(self, resp: CmdResponse<Result<()>>) -> BargraphCmd {
+        match self {
+            BargraphCmdKind::Fast => BargraphCmd::StartBlink { resp },
+            BargraphCmdKind::Medium => BargraphCmd::MediumBlink { resp },
+            BargraphCmdKind::Slow => BargraphCmd::SlowBlink { resp },
+        }
+    }
+}
 
-        let sleep = time::sleep(Duration::new(900, 0));
-        tokio::pin!(sleep);
+async fn wait_stage(
+    recv: &mut sync::mpsc::Receiver<BlinkInfo>,
+    shutdown: &mut sync::watch::Receiver<ServerState>,
+    amt: Duration,
+) -> StageResult {
+    let sleep = time::sleep(amt);
+    tokio::pin!(sleep);
 
-        loop {
-            tokio::select! {
-                // Recv can fail... bail completely from function if so.
-                r = recv.recv() => {
-                    if let Some(bi) = r {
-                        if let BlinkInfo::LedSet(_) = bi {
-                            continue 'blink_timer_reset;
-                        } else {
-                            // LED cleared... cancel.
-                            break 'blink_timer_reset;
-                        }
-                    } else {
-                        return;
-                    }
-                },
-                _ = &mut sleep => {
-                    break 'blink_timer_reset;
+    loop {
+        tokio::select! {
+            r = recv.recv() => {
+                return match r {
+                    Some(BlinkInfo::LedSet(_)) => StageResult::Reset,
+                    Some(BlinkInfo::LedClear(_)) => StageResult::Stop { shutdown: false },
+                    None => StageResult::Stop { shutdown: false },
+                };
+            }
+            _ = shutdown.changed() => {
+                if *shutdown.borrow() == ServerState::ShuttingDown {
+                    return StageResult::Stop { shutdown: true };
                 }
             }
+            _ = &mut sleep => return StageResult::Elapsed,
         }
     }
+}
 
+async fn stop_blink(send: &sync::mpsc::Sender<BargraphCmd>) {
     let (resp, resp_rx) = sync::oneshot::channel();
-    send.send(BargraphCmd::StopBlink { resp }).await;
-    resp_rx.await;
+    let _ = send.send(BargraphCmd::StopBlink { resp }).await;
+    let _ = resp_rx.await;
 }
 
 pub struct BlockingEventLoop {
@@ -313,6 +331,29 @@ impl BlockingEventLoop {
                             return;
                         }
                     }
+                    BargraphCmd::Reinit { device, addr, resp } => {
+                        // Re-open the bus and re-run initialization in
+                        // place, e.g. to recover a wedged HT16K33 after
+                        // SIGHUP, without tearing down this task.
+                        let res = Self::init(device, addr).and_then(|mut b| {
+                            b.set_dimming(Dimming::BRIGHTNESS_3_16)?;
+                            Ok(b)
+                        });
+
+                        match res {
+                            Ok(new_bargraph) => {
+                                bargraph = new_bargraph;
+                                if let Err(_) = resp.send(Ok(())) {
+                                    return;
+                                }
+                            }
+                            Err(e) => {
+                                if let Err(_) = resp.send(Err(e)) {
+                                    return;
+                                }
+                            }
+                        }
+                    }
                 }
             } else {
                 // Only happens if the req_rx channel has closed.
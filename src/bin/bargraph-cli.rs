@@ -1,10 +1,14 @@
-use std::{cell::RefCell, error::Error};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::{error::Error, fs, thread};
 
 use cliargs_t::{Command, CommandInformation, Commander};
 use eyre::{bail, eyre, Result};
 use ht16k33::{Dimming, Display};
-use linux_embedded_hal::I2cdev;
+use linux_embedded_hal::{Delay, I2cdev};
 use reedline::{DefaultPrompt, Reedline, Signal};
+use udev::{EventType, MonitorBuilder};
 use wb_notifier::bargraph::driver::{Bargraph, LedColor};
 
 // trait CommandHelpers {
@@ -15,25 +19,119 @@ use wb_notifier::bargraph::driver::{Bargraph, LedColor};
 
 // }
 
+/// Address block HT16K33 bargraph controllers respond on.
+const HT16K33_RANGE: RangeInclusive<u8> = 0x70..=0x77;
+/// Address block MCP23008 I/O expanders (as used by the HD44780 LCD
+/// backpack) respond on; scanned so `scan` can tell the two controllers
+/// apart even though this CLI only drives the bargraph.
+const MCP23008_RANGE: RangeInclusive<u8> = 0x20..=0x27;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Bargraph,
+    Lcd,
+}
+
+impl Kind {
+    fn label(self) -> &'static str {
+        match self {
+            Kind::Bargraph => "bargraph",
+            Kind::Lcd => "lcd",
+        }
+    }
+}
+
+/// Lists every `/dev/i2c-*` node via sysfs (rather than globbing `/dev`
+/// directly), mirroring how the kernel itself enumerates `i2c-dev`
+/// instances.
+fn i2c_bus_nodes() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir("/sys/class/i2c-dev") else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| PathBuf::from("/dev").join(e.file_name()))
+        .collect()
+}
+
+/// Probes `addr` on the bus at `path` with a zero-length write; an `Ok`
+/// means something ACKed, regardless of what it actually is.
+fn probe(path: &Path, addr: u8) -> bool {
+    let Ok(mut i2c) = I2cdev::new(path) else {
+        return false;
+    };
+
+    if i2c.set_slave_address(addr as u16).is_err() {
+        return false;
+    }
+
+    i2c.write(addr, &[]).is_ok()
+}
+
+/// Scans every bus in `buses` across [`HT16K33_RANGE`] and
+/// [`MCP23008_RANGE`], returning every `(bus, kind, addr)` that ACKed.
+fn scan(buses: &[PathBuf]) -> Vec<(PathBuf, Kind, u8)> {
+    let mut found = Vec::new();
+
+    for bus in buses {
+        for addr in HT16K33_RANGE {
+            if probe(bus, addr) {
+                found.push((bus.clone(), Kind::Bargraph, addr));
+            }
+        }
+
+        for addr in MCP23008_RANGE {
+            if probe(bus, addr) {
+                found.push((bus.clone(), Kind::Lcd, addr));
+            }
+        }
+    }
+
+    found
+}
+
+/// Opens the first bargraph [`scan`] finds, for `OpenCommand`'s `--auto`
+/// mode and for auto-open on hotplug.
+fn open_first_bargraph(buses: &[PathBuf]) -> Result<Bargraph<I2cdev>> {
+    let (bus, _, addr) = scan(buses)
+        .into_iter()
+        .find(|(_, kind, _)| *kind == Kind::Bargraph)
+        .ok_or_else(|| eyre!("no bargraph controller found on any bus"))?;
+
+    let mut i2c = I2cdev::new(&bus)?;
+    i2c.set_slave_address(addr as u16)?;
+
+    let mut bargraph = Bargraph::new(i2c, addr);
+    bargraph.initialize()?;
+
+    Ok(bargraph)
+}
+
 struct OpenCommand {}
 
 impl Command for OpenCommand {
     fn execute_command(&self, flags: std::collections::HashMap<String, String>) {
         let init = || -> Result<()> {
-            if DEV.with(|f| f.borrow().is_some()) {
+            if DEV.lock().unwrap().is_some() {
                 bail!("device already open");
             }
 
-            let mut i2c = I2cdev::new(flags.get("p").unwrap_or(&"/dev/i2c-1".to_string()))?;
-            let addr: u8 = flags.get("a").map(|s| s.parse()).unwrap_or(Ok(0x70))?;
-            i2c.set_slave_address(addr as u16)?;
+            let bargraph = if flags.contains_key("auto") {
+                open_first_bargraph(&i2c_bus_nodes())?
+            } else {
+                let path = flags.get("p").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("/dev/i2c-1"));
+                let addr: u8 = flags.get("a").map(|s| s.parse()).unwrap_or(Ok(0x70))?;
 
-            let mut bargraph = Bargraph::new(i2c, addr);
-            bargraph.initialize()?;
+                let mut i2c = I2cdev::new(&path)?;
+                i2c.set_slave_address(addr as u16)?;
 
-            DEV.with(|f| {
-                *f.borrow_mut() = Some(bargraph);
-            });
+                let mut bargraph = Bargraph::new(i2c, addr);
+                bargraph.initialize()?;
+                bargraph
+            };
+
+            *DEV.lock().unwrap() = Some(bargraph);
 
             Ok(())
         };
@@ -58,20 +156,58 @@ impl Command for OpenCommand {
                     flag_help: "path",
                     required: false,
                 },
+                cliargs_t::Flag {
+                    identifier: "auto",
+                    flag_help: "scan every bus and open the first bargraph found",
+                    required: false,
+                },
             ],
         }
     }
 }
 
+struct ScanCommand {}
+
+impl Command for ScanCommand {
+    fn execute_command(&self, _flags: std::collections::HashMap<String, String>) {
+        let buses = i2c_bus_nodes();
+
+        if buses.is_empty() {
+            println!("no i2c-dev buses found");
+            return;
+        }
+
+        let found = scan(&buses);
+
+        if found.is_empty() {
+            println!("no bargraph/lcd controllers found on {} bus(es)", buses.len());
+            return;
+        }
+
+        for (bus, kind, addr) in found {
+            println!("{}: {} at 0x{:02x}", bus.display(), kind.label(), addr);
+        }
+    }
+
+    fn get_information(&self) -> CommandInformation {
+        CommandInformation {
+            command_name: "scan",
+            command_help: "scan every i2c bus for bargraph/lcd controllers",
+            flags: vec![],
+        }
+    }
+}
+
 struct SetNCommand {}
 
 impl Command for SetNCommand {
     fn execute_command(&self, flags: std::collections::HashMap<String, String>) {
         let _ = DEV
-            .with(|f| -> Result<()> {
-                let mut dev_ref = f.borrow_mut();
-                let dev = dev_ref.as_mut().ok_or(eyre!("device not open"))?;
-
+            .lock()
+            .unwrap()
+            .as_mut()
+            .ok_or(eyre!("device not open"))
+            .and_then(|dev| -> Result<()> {
                 let number = flags.get("n").unwrap().parse()?;
                 let color = match flags.get("c").unwrap().as_str() {
                     "r" => LedColor::Red,
@@ -115,10 +251,11 @@ struct DimCommand {}
 impl Command for DimCommand {
     fn execute_command(&self, flags: std::collections::HashMap<String, String>) {
         let _ = DEV
-            .with(|f| -> Result<()> {
-                let mut dev_ref = f.borrow_mut();
-                let dev = dev_ref.as_mut().ok_or(eyre!("device not open"))?;
-
+            .lock()
+            .unwrap()
+            .as_mut()
+            .ok_or(eyre!("device not open"))
+            .and_then(|dev| -> Result<()> {
                 let pwm = match flags.get("p").unwrap().parse()? {
                     1 => Dimming::BRIGHTNESS_1_16,
                     2 => Dimming::BRIGHTNESS_2_16,
@@ -166,10 +303,11 @@ struct BlinkCommand {}
 impl Command for BlinkCommand {
     fn execute_command(&self, flags: std::collections::HashMap<String, String>) {
         let _ = DEV
-            .with(|f| -> Result<()> {
-                let mut dev_ref = f.borrow_mut();
-                let dev = dev_ref.as_mut().ok_or(eyre!("device not open"))?;
-
+            .lock()
+            .unwrap()
+            .as_mut()
+            .ok_or(eyre!("device not open"))
+            .and_then(|dev| -> Result<()> {
                 let rate = match flags.get("r").unwrap().as_str() {
                     "on" => Display::ON,
                     "off" => Display::OFF,
@@ -206,10 +344,11 @@ struct SetCommand {}
 impl Command for SetCommand {
     fn execute_command(&self, flags: std::collections::HashMap<String, String>) {
         let _ = DEV
-            .with(|f| -> Result<()> {
-                let mut dev_ref = f.borrow_mut();
-                let dev = dev_ref.as_mut().ok_or(eyre!("device not open"))?;
-
+            .lock()
+            .unwrap()
+            .as_mut()
+            .ok_or(eyre!("device not open"))
+            .and_then(|dev| -> Result<()> {
                 let row = flags.get("r").unwrap().parse()?;
                 let col = flags.get("c").unwrap().parse()?;
                 let state = match flags.get("s").unwrap().as_str() {
@@ -257,11 +396,12 @@ struct ResetCommand {}
 impl Command for ResetCommand {
     fn execute_command(&self, _flags: std::collections::HashMap<String, String>) {
         let _ = DEV
-            .with(|f| -> Result<()> {
-                let mut dev_ref = f.borrow_mut();
-                let dev = dev_ref.as_mut().ok_or(eyre!("device not open"))?;
+            .lock()
+            .unwrap()
+            .as_mut()
+            .ok_or(eyre!("device not open"))
+            .and_then(|dev| -> Result<()> {
                 dev.initialize()?;
-
                 Ok(())
             })
             .map_err(|e| {
@@ -278,22 +418,94 @@ impl Command for ResetCommand {
     }
 }
 
-thread_local! {
-    pub static DEV: RefCell<Option<Bargraph<I2cdev>>> = RefCell::new(None);
+struct SelfTestCommand {}
+
+impl Command for SelfTestCommand {
+    fn execute_command(&self, _flags: std::collections::HashMap<String, String>) {
+        let _ = DEV
+            .lock()
+            .unwrap()
+            .as_mut()
+            .ok_or(eyre!("device not open"))
+            .and_then(|dev| -> Result<()> {
+                dev.self_test(&mut Delay {})?;
+                Ok(())
+            })
+            .map_err(|e| {
+                eprintln!("error running self-test: {}", e);
+            });
+    }
+
+    fn get_information(&self) -> CommandInformation {
+        CommandInformation {
+            command_name: "selftest",
+            command_help: "exercise every LED/dimming/blink level, then restore prior state",
+            flags: vec![],
+        }
+    }
+}
+
+/// Shared (rather than thread-local) so the hotplug watcher thread and the
+/// REPL's command thread can both reach whatever bargraph is currently open.
+static DEV: Mutex<Option<Bargraph<I2cdev>>> = Mutex::new(None);
+
+/// Watches for `i2c-dev` nodes appearing/disappearing, as the smithay udev
+/// backend does for its own device discovery, and auto-opens the first
+/// bargraph it sees show up whenever none is already open. Runs until the
+/// udev socket errors, logging and carrying on for any one bad event.
+fn hotplug_watch() -> Result<()> {
+    let socket = MonitorBuilder::new()?.match_subsystem("i2c-dev")?.listen()?;
+
+    for event in socket.iter() {
+        match event.event_type() {
+            EventType::Add => {
+                println!("i2c-dev: {} appeared", event.device().devnode().map_or_else(|| "?".into(), |p| p.display().to_string()));
+
+                if DEV.lock().unwrap().is_some() {
+                    continue;
+                }
+
+                if let Ok(bargraph) = open_first_bargraph(&i2c_bus_nodes()) {
+                    println!("auto-opened bargraph on hotplug");
+                    *DEV.lock().unwrap() = Some(bargraph);
+                }
+            }
+            EventType::Remove => {
+                println!("i2c-dev: {} disappeared", event.device().devnode().map_or_else(|| "?".into(), |p| p.display().to_string()));
+
+                // `DEV` holds at most one bargraph, so any removal event
+                // means it was the one that just vanished; clear it so the
+                // `Add` arm above is willing to auto-reopen on replug
+                // instead of believing a now-dead handle is still good.
+                *DEV.lock().unwrap() = None;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
+    thread::spawn(|| {
+        if let Err(e) = hotplug_watch() {
+            eprintln!("hotplug watcher stopped: {}", e);
+        }
+    });
+
     let mut line_editor = Reedline::create();
     let prompt = DefaultPrompt::default();
 
     let open: Box<dyn Command> = Box::new(OpenCommand {});
+    let scan: Box<dyn Command> = Box::new(ScanCommand {});
     let setn: Box<dyn Command> = Box::new(SetNCommand {});
     let dim: Box<dyn Command> = Box::new(DimCommand {});
     let blink: Box<dyn Command> = Box::new(BlinkCommand {});
     let set: Box<dyn Command> = Box::new(SetCommand {});
     let reset: Box<dyn Command> = Box::new(ResetCommand {});
+    let selftest: Box<dyn Command> = Box::new(SelfTestCommand {});
 
-    let mut commands = vec![open, setn, dim, blink, set, reset];
+    let mut commands = vec![open, scan, setn, dim, blink, set, reset, selftest];
     let cmdr = Commander::new(&mut commands);
 
     loop {
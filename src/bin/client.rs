@@ -34,6 +34,8 @@ mod client {
         Ack(AckSubCommand),
         ConfigBargraph(ConfigBargraphSubCommand),
         ConfigLcd(ConfigLcdSubCommand),
+        Discover(DiscoverSubCommand),
+        Schedule(ScheduleSubCommand),
     }
 
     #[derive(FromArgs, PartialEq, Debug)]
@@ -78,6 +80,43 @@ mod client {
         pub back: Option<SetBacklight>,
     }
 
+    #[derive(FromArgs, PartialEq, Debug)]
+    #[argh(subcommand, name = "discover")]
+    /// find wb-notifier daemons on the LAN via mDNS instead of a hardcoded address
+    pub struct DiscoverSubCommand {
+        /// how long to listen for responses
+        #[argh(
+            option,
+            short = 't',
+            from_str_fn(duration_parse),
+            default = "Duration::from_secs(2)"
+        )]
+        pub timeout: Duration,
+    }
+
+    #[derive(FromArgs, PartialEq, Debug)]
+    #[argh(subcommand, name = "schedule")]
+    /// queue a notify to fire later instead of immediately
+    pub struct ScheduleSubCommand {
+        /// message number/LED to bind to
+        #[argh(option, short = 'l')]
+        pub num: Option<u8>,
+        /// status level of message
+        #[argh(option, short = 's', from_str_fn(status_parse))]
+        pub status: Option<Status>,
+        /// message to send to LCD
+        #[argh(option, short = 'm')]
+        pub msg: Option<String>,
+        /// fire once, at this many seconds since the Unix epoch; requires
+        /// the server's clock to be synced (see --ntp-server)
+        #[argh(option)]
+        pub at: Option<u64>,
+        /// fire every this many seconds, starting this many seconds from
+        /// now; mutually exclusive with --at
+        #[argh(option)]
+        pub every: Option<u32>,
+    }
+
     fn sock_parse(addr: &str) -> Result<SocketAddr, String> {
         addr.parse().map_err(|e: AddrParseError| e.to_string())
     }
@@ -145,6 +184,13 @@ use client::*;
 fn main() -> Result<()> {
     let args: ClientArgs = argh::from_env();
 
+    if let Cmd::Discover(DiscoverSubCommand { timeout }) = &args.cmd {
+        for (instance, addr) in Client::discover(*timeout)? {
+            println!("{instance}\t{addr}");
+        }
+        return Ok(());
+    }
+
     let addr = match args.addr {
         Some(a) => a,
         None => env::var("WBN_SERVER_ADDR")
@@ -159,17 +205,13 @@ fn main() -> Result<()> {
 
     match args.cmd {
         Cmd::Notify(NotifySubCommand { num, status, msg }) => {
-            client.notify(
-                Notify {
-                    num: num.unwrap_or(0),
-                    status: status.unwrap_or(Status::Ok),
-                },
-                &mut buf,
-            )?;
-
-            if let Some(m) = msg {
-                client.send_msg(SendMsg(m), &mut buf)?;
-            }
+            let notify = Notify {
+                num: num.unwrap_or(0),
+                status: status.unwrap_or(Status::Ok),
+                msg,
+            };
+
+            client.notify(notify, &mut buf)?;
         }
         Cmd::Ack(AckSubCommand { num }) => {
             client.ack(Ack { num }, &mut buf)?;
@@ -180,6 +222,28 @@ fn main() -> Result<()> {
         Cmd::ConfigLcd(ConfigLcdSubCommand { back }) => {
             client.set_backlight(back.unwrap_or(SetBacklight::On), &mut buf)?;
         }
+        Cmd::Discover(_) => unreachable!("handled before connecting"),
+        Cmd::Schedule(ScheduleSubCommand {
+            num,
+            status,
+            msg,
+            at,
+            every,
+        }) => {
+            let schedule = match (at, every) {
+                (Some(epoch_secs), None) => Schedule::At { epoch_secs },
+                (None, Some(secs)) => Schedule::Every { secs },
+                _ => eyre::bail!("exactly one of --at or --every must be given"),
+            };
+
+            let notify = Notify {
+                num: num.unwrap_or(0),
+                status: status.unwrap_or(Status::Ok),
+                msg,
+            };
+
+            client.schedule_notify(ScheduleNotify { notify, schedule }, &mut buf)?;
+        }
     }
 
     Ok(())
@@ -5,9 +5,16 @@ mod server {
     pub use smol;
     pub use wb_notifier_proto::Device;
     pub use wb_notifier_server::Server;
+    #[cfg(feature = "mqtt")]
+    pub use wb_notifier_server::mqtt;
+    #[cfg(feature = "serial")]
+    pub use wb_notifier_server::serial;
+    pub use wb_notifier_server::sntp;
 
     pub use smol::LocalExecutor;
     pub use std::net::Ipv4Addr;
+    #[cfg(feature = "scpi")]
+    pub use std::net::SocketAddr;
     pub use std::rc::Rc;
 
     pub use argh::{self, FromArgs};
@@ -23,19 +30,91 @@ mod server {
         pub cfg_file: Option<String>,
         /// do not exit if communication failure with device
         #[argh(switch, short = 'r')]
-        #[allow(unused)]
         pub relaxed: bool,
         /// port to bind to
         #[argh(option, short = 'p', default = "12000")]
         pub port: u16,
+        /// transport to serve requests over: "udp" (default) or "serial:<path>"
+        #[argh(option, default = "TransportArg::Udp", from_str_fn(transport_parse))]
+        pub transport: TransportArg,
+        /// MQTT broker to bridge notifications to/from, as "host:port"
+        #[cfg(feature = "mqtt")]
+        #[argh(option, from_str_fn(mqtt_broker_parse))]
+        pub mqtt_broker: Option<(String, u16)>,
+        /// client id to present to the MQTT broker
+        #[cfg(feature = "mqtt")]
+        #[argh(option, default = "String::from(\"wb-notifier\")")]
+        pub mqtt_client_id: String,
+        /// bind address for the SCPI-style ASCII command interface,
+        /// alongside the binary protocol
+        #[cfg(feature = "scpi")]
+        #[argh(option)]
+        pub scpi: Option<SocketAddr>,
+        /// mDNS service instance name to advertise as, e.g. "bench1";
+        /// advertising is off unless this is set
+        #[cfg(feature = "mdns")]
+        #[argh(option)]
+        pub mdns_instance: Option<String>,
+        /// mDNS host name to advertise (defaults to "<mdns-instance>.local.")
+        #[cfg(feature = "mdns")]
+        #[argh(option)]
+        pub mdns_host: Option<String>,
+        /// NTP server to sync the clock against, so `schedule` can accept
+        /// an absolute time instead of just a relative one
+        #[argh(option)]
+        pub ntp_server: Option<String>,
         /// i2c bus to connect to
         #[argh(positional)]
         pub dev: String,
     }
 
+    #[derive(Debug)]
+    pub enum TransportArg {
+        Udp,
+        #[cfg(feature = "serial")]
+        Serial(String),
+    }
+
+    pub fn transport_parse(transport: &str) -> Result<TransportArg, String> {
+        if transport == "udp" {
+            return Ok(TransportArg::Udp);
+        }
+
+        if let Some(path) = transport.strip_prefix("serial:") {
+            #[cfg(feature = "serial")]
+            return Ok(TransportArg::Serial(path.to_string()));
+
+            #[cfg(not(feature = "serial"))]
+            {
+                let _ = path;
+                return Err("serial transport support not compiled in".to_string());
+            }
+        }
+
+        Err(format!(r#"expected "udp" or "serial:<path>", got {transport}"#))
+    }
+
+    #[cfg(feature = "mqtt")]
+    pub fn mqtt_broker_parse(broker: &str) -> Result<(String, u16), String> {
+        let (host, port) = broker
+            .rsplit_once(':')
+            .ok_or_else(|| format!(r#"expected "host:port", got {broker}"#))?;
+        let port: u16 = port.parse().map_err(|_| format!("invalid port {port}"))?;
+
+        Ok((host.to_string(), port))
+    }
+
     #[derive(Deserialize, Hash)]
     pub struct WbInfo {
         pub devices: Vec<Device>,
+        /// Other wb-notifier daemons to mirror notify/ack to.
+        #[serde(default)]
+        pub peers: Vec<std::net::SocketAddr>,
+        /// Whether notify/ack mirrored in from a peer should be mirrored
+        /// on to `peers` in turn (full mesh) rather than only applied
+        /// locally.
+        #[serde(default)]
+        pub rebroadcast: bool,
     }
 }
 
@@ -64,7 +143,51 @@ fn main() -> Result<()> {
             .try_deserialize::<WbInfo>()?
     };
 
-    let server = Server::new((Ipv4Addr::new(0, 0, 0, 0), args.port).into(), cfgs.devices);
+    let server = Server::new((Ipv4Addr::new(0, 0, 0, 0), args.port).into(), cfgs.devices)
+        .with_relaxed(args.relaxed)
+        .with_peers(cfgs.peers, cfgs.rebroadcast)
+        .with_device_config(dirs.config_dir().join("devices.json"));
+    let server = match args.transport {
+        TransportArg::Udp => server,
+        #[cfg(feature = "serial")]
+        TransportArg::Serial(path) => server.with_serial(serial::SerialConfig {
+            path,
+            baud: serial::DEFAULT_BAUD,
+        }),
+    };
+
+    #[cfg(feature = "mqtt")]
+    let server = match args.mqtt_broker {
+        Some((host, port)) => server.with_mqtt(mqtt::MqttConfig {
+            host,
+            port,
+            client_id: args.mqtt_client_id,
+        }),
+        None => server,
+    };
+
+    #[cfg(feature = "scpi")]
+    let server = match args.scpi {
+        Some(addr) => server.with_scpi(addr),
+        None => server,
+    };
+
+    #[cfg(feature = "mdns")]
+    let server = match args.mdns_instance {
+        Some(instance) => {
+            let host = args
+                .mdns_host
+                .unwrap_or_else(|| format!("{instance}.local."));
+            server.with_mdns(instance, host)
+        }
+        None => server,
+    };
+
+    let server = match args.ntp_server {
+        Some(ntp_server) => server.with_sntp(sntp::SntpConfig { server: ntp_server }),
+        None => server,
+    };
+
     let ex = Rc::new(LocalExecutor::new());
     smol::block_on(ex.run(server.main_loop(ex.clone())))?;
     Ok(())
@@ -0,0 +1,9 @@
+/// Lifecycle state broadcast to the background `blink_task` over a
+/// `tokio::sync::watch` channel so it can wind down on its own terms
+/// instead of being aborted. The blocking I2C event loop doesn't watch
+/// it; it exits once its command channel closes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServerState {
+    Running,
+    ShuttingDown,
+}
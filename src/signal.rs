@@ -0,0 +1,68 @@
+//! SIGINT/SIGTERM/SIGHUP handling for the tokio-based daemon in
+//! `bargraph::tasks`. Compiles out entirely on platforms without Unix
+//! signals.
+
+#[cfg(unix)]
+mod imp {
+    use eyre::Result;
+    use tokio::signal::unix::{signal, SignalKind};
+    use tokio::sync::{mpsc, oneshot, watch};
+
+    use crate::bargraph::tasks::BargraphCmd;
+    use crate::server::ServerState;
+
+    /// Listens for SIGINT/SIGTERM (graceful shutdown) and SIGHUP (reload)
+    /// for as long as the process runs.
+    ///
+    /// On SIGINT/SIGTERM, `shutdown` is flipped to `ServerState::ShuttingDown`
+    /// so `blink_task` can unwind cleanly, and this task then waits on
+    /// `shutdown_complete` before returning so the caller knows the I2C
+    /// device has been left in a known state.
+    ///
+    /// On SIGHUP, `BargraphCmd::Reinit` is sent to the `BlockingEventLoop`
+    /// so a wedged HT16K33 (or a changed device path/address) can be
+    /// recovered without restarting the daemon.
+    pub async fn run(
+        shutdown: watch::Sender<ServerState>,
+        mut shutdown_complete: mpsc::Receiver<()>,
+        cmd: mpsc::Sender<BargraphCmd>,
+        device: String,
+        addr: u8,
+    ) -> Result<()> {
+        let mut sigint = signal(SignalKind::interrupt())?;
+        let mut sigterm = signal(SignalKind::terminate())?;
+        let mut sighup = signal(SignalKind::hangup())?;
+
+        loop {
+            tokio::select! {
+                _ = sigint.recv() => break,
+                _ = sigterm.recv() => break,
+                _ = sighup.recv() => {
+                    let (resp, resp_rx) = oneshot::channel();
+                    if cmd
+                        .send(BargraphCmd::Reinit {
+                            device: device.clone(),
+                            addr,
+                            resp,
+                        })
+                        .await
+                        .is_ok()
+                    {
+                        let _ = resp_rx.await;
+                    }
+                }
+            }
+        }
+
+        let _ = shutdown.send(ServerState::ShuttingDown);
+        // Wait for every shutdown-complete sender to drop, i.e. every
+        // background task has unwound and the I2C device is in a known
+        // state.
+        let _ = shutdown_complete.recv().await;
+
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+pub use imp::run;